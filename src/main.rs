@@ -1,196 +1,7276 @@
-use clap::{Parser, Subcommand};
+use age::secrecy::SecretString;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{
+    engine::{ArgValueCandidates, CompletionCandidate},
+    CompleteEnv, Shell,
+};
 use dirs::home_dir;
-use jiff::Zoned;
-use regex::Regex;
+use flate2::{write::GzEncoder, Compression};
+use jiff::{civil::Date, tz::TimeZone, Span, Timestamp, ToSpan, Zoned};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
-use std::{fs, io, path::PathBuf, process::Command};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::{
+        atomic::{self, AtomicU8},
+        LazyLock,
+    },
+    time::SystemTime,
+};
 
-/// Load config from ~/.notes_cli/config.toml or create a default one
-fn load_or_init_config() -> Config {
-    let config_dir = home_dir().unwrap().join(".notes_cli");
-    let config_file = config_dir.join("config.toml");
-    let default = Config {
-        notes_dir: config_dir.join("notes"),
-        editor: "nano".into(),
+/// How many times `-v` was given; 0 keeps nn quiet
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// Log a message to stderr if `-v` was given at least `level` times
+macro_rules! verbose {
+    ($level:expr, $($arg:tt)*) => {
+        if VERBOSITY.load(atomic::Ordering::Relaxed) >= $level {
+            eprintln!("nn: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// The directory holding nn's config, `~/.notes_cli`
+fn config_dir() -> io::Result<PathBuf> {
+    Ok(home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not find home directory"))?
+        .join(".notes_cli"))
+}
+
+/// The config file to use: the `--config` path if given, otherwise
+/// `~/.notes_cli/config.toml`
+fn config_file(flag: Option<PathBuf>) -> io::Result<PathBuf> {
+    match flag {
+        Some(path) => Ok(path),
+        None => Ok(config_dir()?.join("config.toml")),
+    }
+}
+
+/// Load config from `config_file` or create a default one there, asking on
+/// the terminal how to handle a broken config
+fn load_or_init_config(config_file: &Path) -> io::Result<Config> {
+    if io::stdin().is_terminal() {
+        load_or_init_config_with(config_file, Some(&confirm))
+    } else {
+        load_or_init_config_with(config_file, None)
+    }
+}
+
+/// Asks the user a yes/no question, like [`confirm`]
+type Confirm = dyn Fn(&str) -> io::Result<bool>;
+
+/// Load config from `config_file` or create a default one there, with notes
+/// kept in a `notes` directory beside it. Problems with an existing file are
+/// put to `ask`; without it, unknown keys are only warned about and a file
+/// that does not parse is an error.
+fn load_or_init_config_with(config_file: &Path, ask: Option<&Confirm>) -> io::Result<Config> {
+    let config_dir = config_file.parent().unwrap_or(Path::new(""));
+    let default = Config::new(config_dir.join("notes"));
+
+    if !config_file.exists() {
+        verbose!(1, "creating default config at {}", config_file.display());
+        fs::create_dir_all(config_dir)?;
+        let toml_str = toml::to_string(&default).map_err(io::Error::other)?;
+        fs::write(config_file, toml_str)?;
+        fs::create_dir_all(&default.notes_dir)?;
+        Ok(default)
+    } else {
+        verbose!(1, "loading config from {}", config_file.display());
+        let toml_str = fs::read_to_string(config_file)?;
+        // check against toml keys
+        match parse_config(&toml_str) {
+            Ok((config, unknown)) if unknown.is_empty() => Ok(config),
+            Ok((config, unknown)) => {
+                eprintln!(
+                    "Warning: unknown keys in {}: {}",
+                    config_file.display(),
+                    unknown.join(", ")
+                );
+                let accept = match ask {
+                    Some(ask) => ask("Continue with this config?")?,
+                    None => true,
+                };
+                if !accept {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid config at {}", config_file.display()),
+                    ))
+                } else {
+                    Ok(config)
+                }
+            }
+            Err(e) => {
+                eprintln!("Error loading nn config: {}", e);
+                let accept = match ask {
+                    Some(ask) => ask("Continue with default config?")?,
+                    None => false,
+                };
+                if !accept {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid config at {}", config_file.display()),
+                    ))
+                } else {
+                    Ok(default)
+                }
+            }
+        }
+    }
+}
+
+/// Open the config file in the editor, creating it first if needed, and warn
+/// if the edited file no longer parses or has unknown keys
+fn edit_config(config_file: &Path, editor_override: Option<String>) -> io::Result<()> {
+    if !config_file.exists() {
+        load_or_init_config(config_file)?;
+    }
+    // a broken config should still be fixable, so fall back to the defaults
+    let mut editor_config = fs::read_to_string(config_file)
+        .ok()
+        .and_then(|toml_str| parse_config(&toml_str).ok())
+        .map(|(config, _)| config)
+        .unwrap_or_else(|| Config::new(PathBuf::new()));
+    editor_config.editor_override = editor_override;
+    open_editor(config_file, &resolve_editor(&editor_config))?;
+
+    match parse_config(&fs::read_to_string(config_file)?) {
+        Ok((_, unknown)) if !unknown.is_empty() => eprintln!(
+            "Warning: unknown keys in {}: {}",
+            config_file.display(),
+            unknown.join(", ")
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: {} does not parse: {}", config_file.display(), e),
+    }
+    Ok(())
+}
+
+/// Parse a config file, also returning the dotted paths of any keys that
+/// were not recognized and so would otherwise be silently ignored
+fn parse_config(toml_str: &str) -> Result<(Config, Vec<String>), toml::de::Error> {
+    let mut unknown = Vec::new();
+    let mut config: Config =
+        serde_ignored::deserialize(toml::Deserializer::new(toml_str), |path| {
+            unknown.push(path.to_string())
+        })?;
+    config.expand_paths();
+    Ok((config, unknown))
+}
+
+/// Expand a leading `~` to `home` and `$VAR` or `${VAR}` to the variable's
+/// value as looked up through `env`; unknown variables are left as written
+fn expand_path(path: &Path, home: Option<&Path>, env: impl Fn(&str) -> Option<String>) -> PathBuf {
+    static VAR_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\$(?:\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))").unwrap()
+    });
+    let Some(text) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    let expanded = VAR_RE.replace_all(text, |caps: &regex::Captures| {
+        let name = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        env(name).unwrap_or_else(|| caps[0].to_string())
+    });
+    match (expanded.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            home.join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(expanded.as_ref()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Config {
+    notes_dir: PathBuf,
+    editor: String,
+    /// Extra arguments passed to the editor before the note path, e.g.
+    /// `["--wait"]` so GUI editors block until the note is closed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    editor_args: Vec<String>,
+    /// strftime pattern for note filenames, relative to `notes_dir`; may
+    /// contain `/` to file notes in subdirectories, e.g. `%Y/%m/%d`
+    #[serde(default = "default_date_format")]
+    date_format: String,
+    /// What new notes are headed with; `date` if `heading_format` is set and
+    /// `filename` otherwise when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heading: Option<HeadingSource>,
+    /// strftime pattern for the date in headings, e.g. `%A, %-d %B %Y`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heading_format: Option<String>,
+    /// File extension of notes, without the dot, e.g. `txt` or `org`
+    #[serde(default = "default_note_extension")]
+    note_extension: String,
+    /// Globs of file and directory names left out of every walk of the notes,
+    /// like `*.log.md` or `generated`; `--ignore` adds more for one run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ignore: Vec<String>,
+    /// Where archived notes are moved to, `<notes_dir>/archive` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive_dir: Option<PathBuf>,
+    /// Template for new notes; `{{date}}`, `{{weekday}}` and `{{title}}` are filled in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<PathBuf>,
+    /// Directory of templates picked by file name with `nn new --template <name>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template_dir: Option<PathBuf>,
+    /// Start new notes with a YAML frontmatter block holding `date` and `tags`
+    #[serde(default)]
+    frontmatter: bool,
+    /// Commit changes to the notes directory after editing or changing notes,
+    /// if it is a git repository
+    #[serde(default)]
+    git_autocommit: bool,
+    /// Shell command run after the editor closes on a note, with the note's
+    /// path in `$NN_NOTE` (and, on Unix, `$1`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after_edit: Option<String>,
+    /// Profile used when `--profile` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_profile: Option<String>,
+    /// IANA time zone deciding which day it is, e.g. `Europe/London`;
+    /// the system time zone if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timezone: Option<String>,
+    /// Whether walks over the notes include the archive directory; set by
+    /// `--include-archived` rather than the config file
+    #[serde(skip)]
+    include_archived: bool,
+    /// Editor command given with `--editor`, used ahead of everything else
+    #[serde(skip)]
+    editor_override: Option<String>,
+    /// Whether to color output, from `--color`
+    #[serde(skip)]
+    color: ColorChoice,
+    /// Whether unreadable notes are errors rather than warnings, from `--strict`
+    #[serde(skip)]
+    strict: bool,
+    /// Whether to print stable tab-separated output for scripts, from `--porcelain`
+    #[serde(skip)]
+    porcelain: bool,
+    /// Named templates picked with `nn new --template <name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    templates: BTreeMap<String, PathBuf>,
+    /// Custom subcommands expanding to other arguments, like `week = "list --from -7d"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<String, String>,
+    /// Named note collections selectable with `--profile`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Where the heading of a new note comes from, set as `heading` in the config
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HeadingSource {
+    /// The note's name, such as `2025-04-09`
+    Filename,
+    /// The note's date, formatted with `heading_format`
+    Date,
+    /// A title asked for on the terminal when the note is created
+    Title,
+    /// No heading at all
+    None,
+}
+
+/// A named note collection overriding the top-level `notes_dir` and editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Profile {
+    notes_dir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    editor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    editor_args: Option<Vec<String>>,
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".into()
+}
+
+fn default_note_extension() -> String {
+    "md".into()
+}
+
+impl Config {
+    /// Expand `~` and environment variables in every path setting
+    fn expand_paths(&mut self) {
+        let home = home_dir();
+        let expand = |path: &Path| expand_path(path, home.as_deref(), |var| env::var(var).ok());
+        self.notes_dir = expand(&self.notes_dir);
+        for path in [
+            &mut self.archive_dir,
+            &mut self.template,
+            &mut self.template_dir,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.templates.values_mut())
+        {
+            *path = expand(path);
+        }
+        for profile in self.profiles.values_mut() {
+            profile.notes_dir = expand(&profile.notes_dir);
+        }
+    }
+
+    /// Create a config with default settings for the given notes directory
+    fn new(notes_dir: PathBuf) -> Self {
+        Config {
+            notes_dir,
+            editor: "nano".into(),
+            editor_args: Vec::new(),
+            date_format: default_date_format(),
+            heading: None,
+            heading_format: None,
+            note_extension: default_note_extension(),
+            ignore: Vec::new(),
+            archive_dir: None,
+            template: None,
+            template_dir: None,
+            frontmatter: false,
+            git_autocommit: false,
+            after_edit: None,
+            default_profile: None,
+            timezone: None,
+            include_archived: false,
+            editor_override: None,
+            color: ColorChoice::Auto,
+            strict: false,
+            porcelain: false,
+            templates: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+        }
+    }
+
+    /// Check that `date_format` can format a date, since formatting note
+    /// paths with a broken one would otherwise panic
+    fn check_date_format(&self) -> io::Result<()> {
+        jiff::fmt::strtime::format(&self.date_format, Date::constant(2000, 1, 1))
+            .map(|_| ())
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "invalid date_format '{}' in config: {}",
+                        self.date_format, e
+                    ),
+                )
+            })
+    }
+
+    /// The current time in the configured time zone, or the system one
+    fn now(&self) -> io::Result<Zoned> {
+        let Some(name) = &self.timezone else {
+            return Ok(Zoned::now());
+        };
+        let tz = jiff::tz::TimeZone::get(name).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown timezone '{}' in config: {}", name, e),
+            )
+        })?;
+        Ok(Zoned::now().with_time_zone(tz))
+    }
+
+    /// Switch to the named profile, or the `default_profile` if no name is
+    /// given, creating its notes directory if needed
+    fn apply_profile(&mut self, name: Option<&str>) -> io::Result<()> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(());
+        };
+        verbose!(1, "using profile {}", name);
+        let profile = self.profiles.get(name).cloned().ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "unknown profile '{}', expected one of: {}",
+                    name,
+                    known.join(", ")
+                ),
+            )
+        })?;
+        fs::create_dir_all(&profile.notes_dir)?;
+        self.notes_dir = profile.notes_dir;
+        if let Some(editor) = profile.editor {
+            self.editor = editor;
+            self.editor_args = profile.editor_args.unwrap_or_default();
+        } else if let Some(editor_args) = profile.editor_args {
+            self.editor_args = editor_args;
+        }
+        Ok(())
+    }
+
+    /// Every named template: the files in `template_dir` by name without their
+    /// extension, then the `templates` table, which wins on a clash
+    fn available_templates(&self) -> io::Result<BTreeMap<String, PathBuf>> {
+        let mut templates = BTreeMap::new();
+        if let Some(dir) = &self.template_dir {
+            let entries = fs::read_dir(dir).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("could not read template_dir {}: {}", dir.display(), e),
+                )
+            })?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden && path.is_file() {
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                    templates.insert(name.into_owned(), path);
+                }
+            }
+        }
+        templates.extend(self.templates.clone());
+        Ok(templates)
+    }
+
+    /// Use the named template from `template_dir` or `templates` for new notes
+    /// instead of the default one
+    fn use_template(&mut self, name: &str) -> io::Result<()> {
+        let templates = self.available_templates()?;
+        let template = templates.get(name).cloned().ok_or_else(|| {
+            let known: Vec<&str> = templates.keys().map(String::as_str).collect();
+            let message = if known.is_empty() {
+                format!("unknown template '{}', none are set in the config", name)
+            } else {
+                format!(
+                    "unknown template '{}', expected one of: {}",
+                    name,
+                    known.join(", ")
+                )
+            };
+            io::Error::new(io::ErrorKind::NotFound, message)
+        })?;
+        verbose!(1, "using template {} ({})", name, template.display());
+        self.template = Some(template);
+        Ok(())
+    }
+
+    /// Where new notes get their heading from
+    fn heading_source(&self) -> HeadingSource {
+        match (self.heading, &self.heading_format) {
+            (Some(source), _) => source,
+            (None, Some(_)) => HeadingSource::Date,
+            (None, None) => HeadingSource::Filename,
+        }
+    }
+
+    /// The directory archived notes are moved into
+    fn archive_dir(&self) -> PathBuf {
+        self.archive_dir
+            .clone()
+            .unwrap_or_else(|| self.notes_dir.join("archive"))
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "nn", version, about = "A normal notes tool")]
+struct Cli {
+    /// Log what nn is doing to stderr; repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Show what commands that change files would do, without changing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Don't commit changes to git, even if `git_autocommit` is set
+    #[arg(long, global = true)]
+    no_commit: bool,
+    /// Use a named profile from the config instead of the default one
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Editor command to use for this run, ahead of $VISUAL, $EDITOR and the config
+    #[arg(long, global = true, value_name = "CMD")]
+    editor: Option<String>,
+    /// Read the config from this file instead of ~/.notes_cli/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Use this notes directory instead of the configured one [env: NN_NOTES_DIR]
+    #[arg(long, global = true, value_name = "PATH")]
+    notes_dir: Option<PathBuf>,
+    /// Fail on notes that cannot be read instead of warning and skipping them
+    #[arg(long, global = true)]
+    strict: bool,
+    /// When to color output; `auto` colors terminals unless NO_COLOR is set
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t)]
+    color: ColorChoice,
+    /// Print stable, tab-separated output from list, search, tags and recent for scripts
+    #[arg(long, global = true)]
+    porcelain: bool,
+    /// Leave out notes and directories whose names match this glob; repeatable
+    #[arg(long, global = true, value_name = "GLOB")]
+    ignore: Vec<String>,
+    /// With no command, only open today's note if it already exists
+    #[arg(long)]
+    no_create: bool,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+/// Offer the dates of existing notes when completing a date argument. This
+/// runs inside the shell's completion hook, so it never prompts or creates a
+/// config and just offers nothing if the config can't be read.
+fn complete_note_dates() -> Vec<CompletionCandidate> {
+    let config = config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("config.toml")))
+        .ok()
+        .and_then(|toml_str| parse_config(&toml_str).ok())
+        .map(|(config, _)| config);
+    let Some(mut config) = config else {
+        return Vec::new();
+    };
+    if config.apply_profile(None).is_err() {
+        return Vec::new();
+    }
+    if let Some(notes_dir) = env::var_os("NN_NOTES_DIR") {
+        config.notes_dir = PathBuf::from(notes_dir);
+    }
+
+    let paths = note_paths(&config).unwrap_or_default();
+    sorted_note_dates(&config, &paths)
+        .into_iter()
+        .rev()
+        .map(|date| CompletionCandidate::new(date.to_string()))
+        .collect()
+}
+
+/// Formats notes can be exported to
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// One HTML page per note plus an index and a tags page
+    Html,
+}
+
+/// When to use ANSI colors, chosen with `--color`
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output going to a `terminal` or not
+    fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => terminal && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// Output formats for `tags`
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum TagFormat {
+    /// One tag per line, aligned with `--count`
+    #[default]
+    Plain,
+    /// Tab-separated `tag<TAB>count` rows
+    Tsv,
+    /// Comma-separated rows with a header line
+    Csv,
+}
+
+/// What `count` totals up
+#[derive(Clone, Copy, ValueEnum)]
+enum CountKind {
+    /// Notes in the collection
+    Notes,
+    /// Distinct tags used across all notes
+    Tags,
+    /// Words in all notes, leaving out headings
+    Words,
+}
+
+/// Help text for date arguments, describing the grammar accepted by `resolve_date`
+const DATE_HELP: &str = "Note date as YYYY-MM-DD, or relative to today: \
+    'today', 'yesterday', 'tomorrow', or [+-]N[d|w|m|y] such as -1, -7d, +1w";
+
+const DELETE_HELP: &str = "Note date (same forms as for other commands), or a glob such as \
+    '2024-*' matching note names; may be left out when --from/--to is given";
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions { shell: Shell },
+    /// Show a month-by-month calendar of the days with notes
+    Calendar {
+        /// Year to show [default: the current year]
+        #[arg(value_parser = clap::value_parser!(i16).range(-9999..=9999))]
+        year: Option<i16>,
+    },
+    /// Find or edit the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Delete a note
+    Delete {
+        #[arg(
+            help = DELETE_HELP,
+            allow_hyphen_values = true,
+            required_unless_present_any = ["from", "to"],
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: Option<String>,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Move the note into the archive directory instead of deleting it
+        #[arg(long)]
+        archive: bool,
+        /// Also delete the files attached to the note with `nn attach`
+        #[arg(long, conflicts_with = "archive")]
+        with_attachments: bool,
+        #[command(flatten)]
+        range: DateRangeArgs,
+    },
+    /// Rename a tag across all notes
+    RenameTag {
+        /// Current tag name, with or without the leading `#`
+        old: String,
+        /// New tag name, with or without the leading `#`
+        new: String,
+        /// Print the lines that would change, diff style, without writing anything
+        #[arg(long)]
+        preview: bool,
+    },
+    /// Open the note for a given date, creating it if needed
+    Edit {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Encrypt the note with a passphrase once the editor closes
+        #[arg(long)]
+        encrypt: bool,
+    },
+    /// Export all notes to another format, leaving the markdown untouched
+    Export {
+        /// Format to export to
+        #[arg(long, value_enum, default_value_t = ExportFormat::Html)]
+        format: ExportFormat,
+        /// Directory to write the exported files to
+        out: PathBuf,
+    },
+    /// Find notes by date or title heading rather than by content
+    Find {
+        query: String,
+        /// Match the query's characters in order rather than as a substring
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Rank notes by how well their name and title fuzzy-match a query
+    #[cfg(feature = "fuzzy")]
+    Fuzzy {
+        /// Text to match; read from stdin if omitted
+        query: Option<String>,
+    },
+    /// Print every line matching a regex as `path:line:text`, like `grep -n`
+    Grep {
+        pattern: String,
+        /// Ignore case when matching the pattern
+        #[arg(short = 'i', long)]
+        case_insensitive: bool,
+        /// Also include notes in the archive directory
+        #[arg(long)]
+        include_archived: bool,
+        /// Write the matches to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// List the notes a note links to with `[[...]]`, flagging missing ones
+    Links {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+    },
+    /// List all notes
+    List {
+        /// Print notes as a JSON array with metadata
+        #[arg(long)]
+        json: bool,
+        /// List the oldest notes first instead of the newest
+        #[arg(long)]
+        oldest_first: bool,
+        /// Print full file paths instead of note dates
+        #[arg(long)]
+        paths: bool,
+        /// Also include notes in the archive directory
+        #[arg(long)]
+        include_archived: bool,
+        /// Show at most N results, then a count of the rest
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        #[command(flatten)]
+        range: DateRangeArgs,
+    },
+    /// Mark an open task done by its line number, as shown by `todos`
+    Done {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Line number of the task within the note
+        line: usize,
+    },
+    /// Merge every file for one date into its canonical note
+    Merge {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Delete the merged source files afterwards
+        #[arg(long)]
+        force: bool,
+    },
+    /// Move a note to a different date
+    #[command(alias = "rename")]
+    Move {
+        /// Date of the note to move (same forms as DATE)
+        #[arg(
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        from: String,
+        /// Date to move the note to (same forms as DATE)
+        #[arg(allow_hyphen_values = true)]
+        to: String,
+        /// Overwrite the destination note if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Update a heading naming the old date without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Open the notes directory in a folder-aware editor or the file manager
+    Open,
+    /// Pin a note so it is easy to find again
+    Pin {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+    },
+    /// List pinned notes
+    Pinned,
+    /// Copy an external markdown file, or a directory of dated ones, into the
+    /// notes, appending to any note that already exists for the date
+    Import {
+        /// File or directory to import
+        path: PathBuf,
+        /// Date for an imported file [default: today] (same forms as DATE)
+        #[arg(long, allow_hyphen_values = true)]
+        date: Option<String>,
+    },
+    /// Show the most recently modified notes
+    Recent {
+        /// Number of notes to show [default: 10]
+        count: Option<usize>,
+        /// Same as COUNT
+        #[arg(long, value_name = "N", conflicts_with = "count")]
+        limit: Option<usize>,
+    },
+    /// Open the undated scratch note, creating it if needed
+    Scratch,
+    /// Create a note, optionally from one of the named templates, and open it
+    New {
+        #[arg(
+            help = "Note date [default: today]; same forms as for other commands",
+            allow_hyphen_values = true
+        )]
+        date: Option<String>,
+        /// Name of a template from `template_dir` or the `templates` table, see `nn templates`
+        #[arg(short, long, value_name = "NAME", conflicts_with = "stdin")]
+        template: Option<String>,
+        /// Write the note from stdin instead of opening the editor
+        #[arg(long)]
+        stdin: bool,
+        /// With --stdin, add to an existing note instead of refusing
+        #[arg(long, requires = "stdin", conflicts_with = "force")]
+        append: bool,
+        /// With --stdin, overwrite an existing note instead of refusing
+        #[arg(long, requires = "stdin")]
+        force: bool,
+    },
+    /// List the named templates available to `nn new --template`
+    Templates,
+    /// Write `index.md`, a table of contents linking every dated note
+    Index,
+    /// Commit, pull --rebase and push the notes directory's git repository
+    Sync,
+    /// List notes written on today's month and day in earlier years
+    OnThisDay {
+        /// Print each note in full instead of its date and heading
+        #[arg(long)]
+        show: bool,
+    },
+    /// Print a randomly picked note, to resurface something forgotten
+    Random {
+        /// Open the note in the editor instead of printing it
+        #[arg(long)]
+        edit: bool,
+        /// Favour older notes, weighting each by its age in days
+        #[arg(long)]
+        old: bool,
+    },
+    /// Show how a note changed in git, by default since the last commit
+    Diff {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Revision to compare the note with, e.g. `HEAD~3` or a commit hash
+        rev: Option<String>,
+    },
+    /// Delete notes left with nothing but their generated heading
+    Clean {
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List open `- [ ]` tasks across all notes, oldest note first
+    Todos {
+        /// Also list completed `- [x]` tasks
+        #[arg(short, long)]
+        all: bool,
+    },
+    /// Open a note and run the `after_edit` hook each time it is saved, until the editor exits
+    #[cfg(feature = "watch")]
+    Watch {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Command to run on each save instead of `after_edit`
+        #[arg(long, value_name = "CMD")]
+        command: Option<String>,
+        /// Also re-export every note to HTML in this directory on each save
+        #[arg(long, value_name = "DIR")]
+        export: Option<PathBuf>,
+    },
+    /// Browse, preview, edit and delete notes in a full-screen interface
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Search all notes for a string
+    Search {
+        query: String,
+        /// Ignore case when matching the query
+        #[arg(short = 'i', long)]
+        case_insensitive: bool,
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+        /// Number of lines of context to show around each match
+        #[arg(short = 'C', long, default_value_t = 0)]
+        context: usize,
+        /// Only print the number of matching lines per note and in total
+        #[arg(short, long, conflicts_with = "context")]
+        count: bool,
+        /// Only print the paths of matching notes; exits with 1 if none match
+        #[arg(short = 'l', long, conflicts_with_all = ["context", "count"])]
+        files_only: bool,
+        /// Print nothing, exiting with 0 as soon as a note matches and 1 if none do
+        #[arg(
+            short,
+            long,
+            conflicts_with_all = ["context", "count", "files_only", "output"]
+        )]
+        quiet: bool,
+        /// Also include notes in the archive directory
+        #[arg(long)]
+        include_archived: bool,
+        /// Write the matches to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Show at most N matching notes, then a count of the rest
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        #[command(flatten)]
+        range: DateRangeArgs,
+    },
+    /// Summarize the notes collection
+    Stats,
+    /// Print a single total for use in scripts, e.g. `$(nn count notes)`
+    Count {
+        #[arg(value_enum)]
+        what: CountKind,
+    },
+    /// Show a note's path, size, timestamps, counts, tags and frontmatter
+    Info {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+    },
+    /// Show current and longest daily-note streaks and recently missed days
+    Streak,
+    /// Print a note to stdout
+    #[command(alias = "cat")]
+    Show {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// Pretty-print the markdown, with colors when writing to a terminal
+        #[arg(long)]
+        render: bool,
+    },
+    /// Append a timestamped bullet to today's note without opening the editor
+    Append {
+        /// Text to append; `-`, or nothing when piped, reads it from stdin
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        text: Vec<String>,
+    },
+    /// Copy a file into the note's attachments folder and link to it from the note
+    Attach {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+        /// File to attach, such as a screenshot or PDF
+        file: PathBuf,
+    },
+    /// Save a timestamped .tar.gz snapshot of the whole notes directory
+    Backup {
+        /// Directory to write the archive to [default: current directory]
+        out: Option<PathBuf>,
+    },
+    /// List the notes that link to a note with `[[...]]`
+    Backlinks {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+    },
+    /// Show all tags used in notes
+    Tags {
+        /// Show how often each tag is used, most frequent first
+        #[arg(long)]
+        count: bool,
+        /// How to print the tags
+        #[arg(long, value_enum, default_value_t)]
+        format: TagFormat,
+        /// Also include notes in the archive directory
+        #[arg(long)]
+        include_archived: bool,
+    },
+    /// List the notes that use a tag
+    Tag {
+        /// Tag name, with or without the leading `#`
+        name: String,
+    },
+    /// Count words, lines and characters of prose in one note or all notes
+    WordCount {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: Option<String>,
+    },
+    /// Remove a note from the pinned notes
+    Unpin {
+        #[arg(
+            help = DATE_HELP,
+            allow_hyphen_values = true,
+            add = ArgValueCandidates::new(complete_note_dates)
+        )]
+        date: String,
+    },
+    /// Open yesterday's note, creating it if needed
+    Yesterday,
+}
+
+/// `--from`/`--to` options restricting a command to notes dated in a window
+#[derive(Args)]
+struct DateRangeArgs {
+    /// Only include notes dated on or after this date (same forms as DATE)
+    #[arg(long, value_name = "DATE", allow_hyphen_values = true)]
+    from: Option<String>,
+    /// Only include notes dated on or before this date (same forms as DATE)
+    #[arg(long, value_name = "DATE", allow_hyphen_values = true)]
+    to: Option<String>,
+}
+
+impl DateRangeArgs {
+    /// Resolve both bounds relative to `today`
+    fn resolve(&self, today: Date) -> Result<DateRange, String> {
+        let resolve = |bound: &Option<String>| {
+            bound
+                .as_deref()
+                .map(|date| resolve_date(date, today))
+                .transpose()
+        };
+        let range = DateRange {
+            from: resolve(&self.from)?,
+            to: resolve(&self.to)?,
+        };
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                return Err(format!(
+                    "invalid date range: --from {} is after --to {}",
+                    from, to
+                ));
+            }
+        }
+        Ok(range)
+    }
+}
+
+/// An inclusive window of dates, either end of which may be open
+#[derive(Debug, Default, Clone, Copy)]
+struct DateRange {
+    from: Option<Date>,
+    to: Option<Date>,
+}
+
+impl DateRange {
+    fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+
+    fn contains(&self, date: Date) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+
+    /// Keep only notes whose filename date falls in the range; undated notes
+    /// are dropped unless the range is unbounded
+    fn retain(&self, config: &Config, paths: &mut Vec<PathBuf>) {
+        if !self.is_unbounded() {
+            paths.retain(|path| note_date(config, path).is_some_and(|date| self.contains(date)));
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the location of the config file
+    Path,
+    /// Open the config file in the editor, then check that it still parses
+    Edit,
+}
+
+/// Parse a `YYYY-MM-DD` date string into a calendar date, rejecting anything
+/// that is not a real date or that could escape the notes directory
+fn parse_date(date: &str) -> Result<Date, String> {
+    static DATE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap());
+    if date.contains("..") || date.contains('/') || date.contains('\\') {
+        return Err(format!(
+            "invalid date '{}': must not contain path components",
+            date
+        ));
+    }
+    if !DATE_RE.is_match(date) {
+        return Err(format!("invalid date '{}': expected YYYY-MM-DD", date));
+    }
+    date.parse()
+        .map_err(|e| format!("invalid date '{}': {}", date, e))
+}
+
+/// Resolve a date argument relative to `today`, accepting `today`, `yesterday`,
+/// `tomorrow` and signed offsets like `-7d` or `+1w` before falling back to
+/// a literal `YYYY-MM-DD` date
+fn resolve_date(input: &str, today: Date) -> Result<Date, String> {
+    static OFFSET_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^([+-])(\d+)([dwmy]?)$").unwrap());
+    let offset = match input {
+        "today" => Some(Span::new()),
+        "yesterday" => Some(Span::new().days(-1)),
+        "tomorrow" => Some(Span::new().days(1)),
+        _ => match OFFSET_RE.captures(input) {
+            Some(caps) => {
+                let invalid =
+                    |reason: String| format!("invalid date offset '{}': {}", input, reason);
+                let mut n = caps[2].parse::<i64>().map_err(|e| invalid(e.to_string()))?;
+                if &caps[1] == "-" {
+                    n = -n;
+                }
+                let span = match &caps[3] {
+                    "w" => Span::new().try_weeks(n),
+                    "m" => Span::new().try_months(n),
+                    "y" => Span::new().try_years(n),
+                    _ => Span::new().try_days(n),
+                };
+                Some(span.map_err(|e| invalid(e.to_string()))?)
+            }
+            None => None,
+        },
+    };
+
+    match offset {
+        Some(span) => today
+            .checked_add(span)
+            .map_err(|e| format!("invalid date offset '{}': {}", input, e)),
+        None => parse_date(input),
+    }
+}
+
+/// Get the path to the note file for a given date
+fn get_note_path(config: &Config, date: Date) -> PathBuf {
+    config.notes_dir.join(format!(
+        "{}.{}",
+        date.strftime(&config.date_format),
+        config.note_extension
+    ))
+}
+
+/// The heading marker for a note file: `* ` for org-mode, `# ` otherwise
+fn heading_marker(path: &Path) -> &'static str {
+    if path.extension().is_some_and(|ext| ext == "org") {
+        "* "
+    } else {
+        "# "
+    }
+}
+
+/// The title for the heading of a new note at `path`: with a `date` heading,
+/// `date` formatted with `heading_format` if that is set and valid (ISO
+/// otherwise), and the note's name for any other heading
+fn note_heading(config: &Config, path: &Path, date: Date) -> String {
+    if config.heading_source() == HeadingSource::Date {
+        let Some(format) = &config.heading_format else {
+            return date.to_string();
+        };
+        match jiff::fmt::strtime::format(format, date) {
+            Ok(heading) => return heading,
+            Err(e) => eprintln!("Warning: invalid heading_format '{}': {}", format, e),
+        }
+    }
+    note_name(config, path)
+}
+
+/// Ask on the terminal for the title of the new note for `date`, giving
+/// `None` if there is no terminal or nothing was entered
+fn prompt_title(date: Date) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    eprint!("Title for the note for {}: ", date);
+    io::stderr().flush().ok()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).ok()?;
+    let title = input.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Fill in the `{{date}}`, `{{weekday}}` and `{{title}}` placeholders of a template
+fn render_template(template: &str, date: Date, title: &str) -> String {
+    template
+        .replace("{{date}}", &date.to_string())
+        .replace("{{weekday}}", &date.strftime("%A").to_string())
+        .replace("{{title}}", title)
+}
+
+/// The initial contents of a new note: the configured template if there is
+/// one, otherwise a heading from the `heading` setting, preceded by
+/// frontmatter if enabled
+fn new_note_contents(config: &Config, path: &Path, date: Date) -> String {
+    let title = path.file_stem().unwrap_or_default().to_string_lossy();
+    let frontmatter = if config.frontmatter {
+        format!("---\ndate: {}\ntags: []\n---\n\n", date)
+    } else {
+        String::new()
+    };
+    if let Some(template) = &config.template {
+        match fs::read_to_string(template) {
+            Ok(template) => return frontmatter + &render_template(&template, date, &title),
+            Err(e) => eprintln!(
+                "Warning: could not read template {}: {}",
+                template.display(),
+                e
+            ),
+        }
+    }
+    let heading = match config.heading_source() {
+        HeadingSource::None => return frontmatter,
+        HeadingSource::Title => {
+            prompt_title(date).unwrap_or_else(|| note_heading(config, path, date))
+        }
+        HeadingSource::Filename | HeadingSource::Date => note_heading(config, path, date),
+    };
+    format!("{}{}{}\n\n", frontmatter, heading_marker(path), heading)
+}
+
+/// Metadata declared in a YAML frontmatter block at the top of a note
+#[derive(Debug, Default, PartialEq)]
+struct Frontmatter {
+    /// Every `key: value` pair in order, with list values kept as written
+    fields: Vec<(String, String)>,
+    /// Tags from the `tags` field, without a leading `#`
+    tags: Vec<String>,
+}
+
+/// Parse a frontmatter block delimited by `---` lines at the very start of a
+/// note. Only flat `key: value` pairs are understood; `tags` may be an inline
+/// list (`[a, b]`) or a block list of `- a` lines.
+fn parse_frontmatter(contents: &str) -> Option<Frontmatter> {
+    let mut lines = contents.lines();
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+
+    let clean = |item: &str| {
+        item.trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .trim_start_matches('#')
+            .to_string()
+    };
+    let mut frontmatter = Frontmatter::default();
+    let mut in_tags = false;
+    for line in lines {
+        if line.trim_end() == "---" {
+            return Some(frontmatter);
+        }
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if in_tags {
+                frontmatter.tags.push(clean(item));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        in_tags = key == "tags";
+        if in_tags {
+            let items = value.trim_start_matches('[').trim_end_matches(']');
+            frontmatter
+                .tags
+                .extend(items.split(',').map(clean).filter(|tag| !tag.is_empty()));
+        }
+        frontmatter
+            .fields
+            .push((key.to_string(), value.to_string()));
+    }
+    // no closing delimiter, so this was not frontmatter after all
+    None
+}
+
+/// Create a note file with its initial contents if it does not already exist,
+/// along with any parent directories implied by the date format
+fn create_note_if_missing(config: &Config, path: &PathBuf, date: Date) -> io::Result<()> {
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, new_note_contents(config, path, date))?;
+    }
+    Ok(())
+}
+
+/// Work out the editor command line, preferring the `--editor` flag, then
+/// `$VISUAL`, then `$EDITOR`, then the configured editor with its
+/// `editor_args`, then `nano`. The environment is looked up through `env` so
+/// the precedence can be tested.
+fn editor_command(config: &Config, env: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let chosen = config
+        .editor_override
+        .clone()
+        .into_iter()
+        .chain(["VISUAL", "EDITOR"].into_iter().filter_map(env));
+    for value in chosen {
+        if !value.trim().is_empty() {
+            // like git, allow the command to carry its own flags, e.g. `code --wait`
+            return value.split_whitespace().map(str::to_string).collect();
+        }
+    }
+    if config.editor.trim().is_empty() {
+        return vec!["nano".to_string()];
+    }
+    std::iter::once(config.editor.clone())
+        .chain(config.editor_args.iter().cloned())
+        .collect()
+}
+
+/// The editor command line for this run, as chosen by [`editor_command`]
+fn resolve_editor(config: &Config) -> Vec<String> {
+    editor_command(config, |var| env::var(var).ok())
+}
+
+/// Open the given file with a resolved editor command line
+fn open_editor(path: &Path, command: &[String]) -> io::Result<()> {
+    let editor = &command[0];
+    verbose!(1, "running {} {}", command.join(" "), path.display());
+    Command::new(editor)
+        .args(&command[1..])
+        .arg(path)
+        .status()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("could not run editor '{}': {}", editor, e),
+            )
+        })?;
+    Ok(())
+}
+
+/// The shell command running `hook` for the note at `path`, which it gets as
+/// `$1` and as `NN_NOTE`
+fn hook_command(hook: &str, path: &Path) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", hook]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", hook, "sh"]).arg(path);
+        command
+    };
+    command.env("NN_NOTE", path);
+    command
+}
+
+/// Run the `after_edit` hook, if one is configured, for a note that was just
+/// edited. A failing hook only produces a warning.
+fn run_after_edit(config: &Config, path: &Path) -> io::Result<()> {
+    let Some(hook) = &config.after_edit else {
+        return Ok(());
+    };
+    verbose!(1, "running after_edit hook {}", hook);
+    match hook_command(hook, path).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Warning: after_edit hook '{}' failed ({})", hook, status),
+        Err(e) => eprintln!("Warning: could not run after_edit hook '{}': {}", hook, e),
+    }
+    Ok(())
+}
+
+/// Editors that open a directory as a workspace rather than a single file
+const WORKSPACE_EDITORS: &[&str] = &["code", "codium", "cursor", "subl", "zed", "atom"];
+
+/// Work out the command that opens the notes directory: the editor itself if
+/// it handles folders, such as `code`, otherwise the platform's file opener
+fn open_dir_command(config: &Config, env: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let editor = editor_command(config, env);
+    let program = Path::new(&editor[0])
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    if WORKSPACE_EDITORS.contains(&program) {
+        // `--wait` would only keep the terminal busy for a whole folder
+        return editor.into_iter().filter(|arg| arg != "--wait").collect();
+    }
+    let opener: &[&str] = if cfg!(target_os = "macos") {
+        &["open"]
+    } else if cfg!(windows) {
+        &["cmd", "/C", "start", ""]
+    } else {
+        &["xdg-open"]
     };
+    opener.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Open the notes directory itself, as a workspace or in the file manager
+fn open_notes_dir(config: &Config, dry_run: bool) -> io::Result<()> {
+    let command = open_dir_command(config, |var| env::var(var).ok());
+    if dry_run {
+        println!(
+            "Would open {} with {}",
+            config.notes_dir.display(),
+            command.join(" ")
+        );
+        return Ok(());
+    }
+    let program = &command[0];
+    Command::new(program)
+        .args(&command[1..])
+        .arg(&config.notes_dir)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run '{}': {}", program, e)))?;
+    Ok(())
+}
+
+/// Run git with the given arguments in the notes directory, discarding its
+/// output, and report whether it succeeded
+fn git(config: &Config, args: &[&str]) -> io::Result<bool> {
+    verbose!(2, "running git {}", args.join(" "));
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(&config.notes_dir)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    Ok(status.success())
+}
+
+/// Run git in the notes directory, capturing its output
+fn git_output(config: &Config, args: &[&str]) -> io::Result<Output> {
+    verbose!(2, "running git {}", args.join(" "));
+    Command::new("git")
+        .arg("-C")
+        .arg(&config.notes_dir)
+        .args(args)
+        .output()
+}
+
+/// Whether the notes directory is inside a git work tree
+fn is_git_repo(config: &Config) -> bool {
+    git(config, &["rev-parse", "--is-inside-work-tree"]).unwrap_or(false)
+}
+
+/// Stage and commit everything in the notes directory if `git_autocommit`
+/// is on and it is a git repository, doing nothing when there are no changes
+fn autocommit(config: &Config, message: &str) -> io::Result<()> {
+    if !config.git_autocommit || !is_git_repo(config) {
+        return Ok(());
+    }
+    git(config, &["add", "--all", "."])?;
+    // `diff --cached --quiet` succeeds when nothing is staged
+    if git(config, &["diff", "--cached", "--quiet"])? {
+        return Ok(());
+    }
+    if !git(config, &["commit", "--quiet", "-m", message])? {
+        eprintln!(
+            "Warning: git commit in {} failed",
+            config.notes_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Commit pending changes (if autocommit is on), then `git pull --rebase` and
+/// `git push` the notes directory. A failed rebase is aborted so the notes
+/// are left as they were.
+fn sync_notes(config: &Config, dry_run: bool) -> io::Result<()> {
+    let dir = config.notes_dir.display();
+    if !is_git_repo(config) {
+        println!("{} is not a git repository, nothing to sync", dir);
+        return Ok(());
+    }
+    let remotes = git_output(config, &["remote"])?;
+    if String::from_utf8_lossy(&remotes.stdout).trim().is_empty() {
+        println!(
+            "{} has no git remote, add one with `git -C {} remote add origin <url>`",
+            dir, dir
+        );
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would pull --rebase and push {}", dir);
+        return Ok(());
+    }
+
+    autocommit(config, "nn: sync")?;
+    let pull = git_output(config, &["pull", "--rebase", "--quiet"])?;
+    if !pull.status.success() {
+        // leave no half-finished rebase behind
+        let aborted = git(config, &["rebase", "--abort"])?;
+        return Err(io::Error::other(format!(
+            "git pull --rebase in {} failed{}:\n{}",
+            dir,
+            if aborted { ", rebase aborted" } else { "" },
+            String::from_utf8_lossy(&pull.stderr).trim()
+        )));
+    }
+    let push = git_output(config, &["push", "--quiet"])?;
+    if !push.status.success() {
+        return Err(io::Error::other(format!(
+            "git push from {} failed:\n{}",
+            dir,
+            String::from_utf8_lossy(&push.stderr).trim()
+        )));
+    }
+    println!("Synced {}", dir);
+    Ok(())
+}
+
+/// Print `git diff [rev] -- <note>` for the note on `date`, comparing the
+/// working tree with `HEAD` unless another revision is given
+fn diff_note(config: &Config, date: Date, rev: Option<&str>) -> io::Result<()> {
+    if !is_git_repo(config) {
+        return Err(io::Error::other(format!(
+            "{} is not a git repository",
+            config.notes_dir.display()
+        )));
+    }
+    let path = get_note_path(config, date);
+    let mut args = vec!["diff"];
+    match config.color {
+        ColorChoice::Always => args.push("--color=always"),
+        ColorChoice::Never => args.push("--color=never"),
+        ColorChoice::Auto => {}
+    }
+    args.push(rev.unwrap_or("HEAD"));
+    verbose!(2, "running git {} -- {}", args.join(" "), path.display());
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(&config.notes_dir)
+        .args(&args)
+        .arg("--")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "git diff of {} failed",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Open the note for the given date in the editor, creating it first if missing
+fn edit_note(config: &Config, date: Date, dry_run: bool) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    if !path.exists() && encrypted_path(&path).exists() {
+        return edit_encrypted_note(config, date, dry_run);
+    }
+    if dry_run {
+        if !path.exists() {
+            println!("Would create {}", path.display());
+        }
+        let editor = resolve_editor(config);
+        println!("Would open {} in {}", path.display(), editor.join(" "));
+        return Ok(());
+    }
+    create_note_if_missing(config, &path, date)?;
+    open_editor(&path, &resolve_editor(config))?;
+    run_after_edit(config, &path)
+}
+
+/// Add a `- HH:MM text` bullet to the end of a note's contents, indenting
+/// any further lines of `text` beneath it
+fn append_entry(contents: &str, time: &str, text: &str) -> String {
+    let mut out = contents.to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    let mut lines = text.trim_end().lines();
+    out.push_str(&format!(
+        "- {} {}\n",
+        time,
+        lines.next().unwrap_or_default()
+    ));
+    for line in lines {
+        if line.trim().is_empty() {
+            out.push('\n');
+        } else {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    out
+}
+
+/// The text to append: the arguments joined with spaces, or all of stdin
+/// when the only argument is `-` or nothing was given and stdin is piped
+fn append_text(args: &[String]) -> io::Result<String> {
+    let from_stdin = match args {
+        [dash] => dash == "-",
+        [] => !io::stdin().is_terminal(),
+        _ => false,
+    };
+    if !from_stdin {
+        if args.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nothing to append, pass some text or pipe it in",
+            ));
+        }
+        return Ok(args.join(" "));
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Append `text` as a timestamped bullet to the note for the day of `now`,
+/// creating the note if needed
+fn append_note(config: &Config, now: &Zoned, text: &str, dry_run: bool) -> io::Result<()> {
+    let date = now.date();
+    let path = get_note_path(config, date);
+    let time = now.strftime("%H:%M").to_string();
+    if dry_run {
+        println!("Would append \"- {} {}\" to {}", time, text, path.display());
+        return Ok(());
+    }
+    create_note_if_missing(config, &path, date)?;
+    let contents = fs::read_to_string(&path)?;
+    fs::write(&path, append_entry(&contents, &time, text))
+}
+
+/// Write `body` as the note for `date`, starting it with the default heading
+/// unless it already opens with one. An existing note is only changed with
+/// `append` (adding `body` to its end) or `force` (replacing it).
+fn write_note(
+    config: &Config,
+    date: Date,
+    body: &str,
+    append: bool,
+    force: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    let exists = note_exists(config, date);
+    if exists && !append && !force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists, pass --append or --force",
+                path.display()
+            ),
+        ));
+    }
+    if exists && !path.exists() {
+        return Err(io::Error::other(format!(
+            "the note for {} is encrypted, use `nn edit` instead",
+            date
+        )));
+    }
+    let (verb, done) = if !exists {
+        ("create", "Created")
+    } else if append {
+        ("append to", "Appended to")
+    } else {
+        ("replace", "Replaced")
+    };
+    if dry_run {
+        println!("Would {} {}", verb, path.display());
+        return Ok(());
+    }
+
+    let contents = if exists && append {
+        let mut contents = fs::read_to_string(&path)?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents + body
+    } else {
+        let marker = heading_marker(&path);
+        let has_heading = body
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| line.starts_with(marker));
+        if has_heading || config.heading_source() == HeadingSource::None {
+            body.to_string()
+        } else {
+            format!(
+                "{}{}\n\n{}",
+                marker,
+                note_heading(config, &path, date),
+                body
+            )
+        }
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, contents)?;
+    println!("{} {}", done, path.display());
+    Ok(())
+}
+
+/// The folder holding the files attached to the note for `date`
+fn attachments_dir(config: &Config, date: Date) -> PathBuf {
+    config.notes_dir.join("attachments").join(date.to_string())
+}
+
+/// A link from the note at `note` to the file at `target`, both below the notes
+/// directory. Images are embedded, and org notes get org link syntax.
+fn attachment_link(config: &Config, note: &Path, target: &Path) -> String {
+    let depth = note
+        .parent()
+        .and_then(|parent| parent.strip_prefix(&config.notes_dir).ok())
+        .map_or(0, |parent| parent.components().count());
+    let relative = target.strip_prefix(&config.notes_dir).unwrap_or(target);
+    let href = std::iter::repeat_n("..".to_string(), depth)
+        .chain(
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join("/");
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    if heading_marker(note) == "* " {
+        return format!("[[file:{}][{}]]", href, name);
+    }
+    // angle brackets keep spaces and parentheses from ending the link early
+    let href = if href.contains([' ', '(', ')']) {
+        format!("<{}>", href)
+    } else {
+        href
+    };
+    let is_image = target.extension().is_some_and(|ext| {
+        ["png", "jpg", "jpeg", "gif", "svg", "webp"]
+            .contains(&ext.to_string_lossy().to_lowercase().as_str())
+    });
+    let bang = if is_image { "!" } else { "" };
+    format!("{}[{}]({})", bang, name, href)
+}
+
+/// Copy `file` into the attachments folder for `date`, renaming it if the name
+/// is taken, and append a link to it to the note, creating the note if needed
+fn attach_file(config: &Config, date: Date, file: &Path, dry_run: bool) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    if note_exists(config, date) && !path.exists() {
+        return Err(io::Error::other(format!(
+            "the note for {} is encrypted, attachments can't be linked from it",
+            date
+        )));
+    }
+    if !file.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a file", file.display()),
+        ));
+    }
+    let file_name = file.file_name().unwrap_or_default();
+    let target = unique_path(&attachments_dir(config, date), Path::new(file_name));
+    let link = attachment_link(config, &path, &target);
+    if dry_run {
+        println!("Would copy {} to {}", file.display(), target.display());
+        println!("Would append \"{}\" to {}", link, path.display());
+        return Ok(());
+    }
+
+    fs::create_dir_all(target.parent().unwrap())?;
+    fs::copy(file, &target)?;
+    create_note_if_missing(config, &path, date)?;
+    let mut contents = fs::read_to_string(&path)?;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&link);
+    contents.push('\n');
+    fs::write(&path, contents)?;
+    println!("Attached {} to the note for {}", target.display(), date);
+    Ok(())
+}
+
+/// Remove the attachments folder of the note at `path`, if it has one
+fn delete_attachments(config: &Config, path: &Path, dry_run: bool) -> io::Result<()> {
+    let Some(date) = note_date(config, path) else {
+        return Ok(());
+    };
+    let dir = attachments_dir(config, date);
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would delete {}", dir.display());
+        return Ok(());
+    }
+    fs::remove_dir_all(&dir)?;
+    println!("Deleted attachments in {}", dir.display());
+    Ok(())
+}
+
+/// Add `source` to the note for `date`, creating the note or appending after a
+/// separator, and report what was done
+fn import_file(config: &Config, source: &Path, date: Date, dry_run: bool) -> io::Result<()> {
+    let target = get_note_path(config, date);
+    if !target.exists() && encrypted_path(&target).exists() {
+        return Err(note_encrypted(date, "use `nn edit` to add to it"));
+    }
+    let exists = target.exists();
+    let action = if exists { "appended to" } else { "created" };
+    if dry_run {
+        println!(
+            "Would import {} ({} {})",
+            source.display(),
+            action,
+            target.display()
+        );
+        return Ok(());
+    }
+
+    let imported = fs::read_to_string(source).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("could not read {}: {}", source.display(), e),
+        )
+    })?;
+    if exists {
+        let mut contents = fs::read_to_string(&target)?;
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str("\n---\n\n");
+        contents.push_str(&imported);
+        fs::write(&target, contents)?;
+    } else {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, imported)?;
+    }
+    println!("Imported {} ({} {})", source.display(), action, date);
+    Ok(())
+}
+
+/// Import a file as the note for `date`, or every markdown file in a
+/// directory as the note for the date in its filename
+fn import_notes(config: &Config, path: &Path, date: Date, dry_run: bool) -> io::Result<()> {
+    if !path.is_dir() {
+        return import_file(config, path, date, dry_run);
+    }
+    let mut sources = Vec::new();
+    walk_dir(path, &config.note_extension, &[], &[], &mut sources)?;
+    sources.sort();
+    for source in sources {
+        let stem = source
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let date = Date::strptime(&config.date_format, stem)
+            .ok()
+            .or_else(|| parse_date(stem).ok());
+        match date {
+            Some(date) => import_file(config, &source, date, dry_run)?,
+            None => eprintln!("Skipped {}: no date in its filename", source.display()),
+        }
+    }
+    Ok(())
+}
+
+/// The file name of a backup taken at `now`, e.g. `notes-backup-20250409-1530.tar.gz`
+fn backup_file_name(now: &Zoned) -> String {
+    format!("notes-backup-{}.tar.gz", now.strftime("%Y%m%d-%H%M"))
+}
+
+/// Write the whole of `notes_dir` as a gzipped tarball to `target`, with the
+/// notes under a top-level `notes/` directory
+fn write_backup(notes_dir: &Path, target: &Path) -> io::Result<()> {
+    let file = fs::File::create(target)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    archive.append_dir_all("notes", notes_dir)?;
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// `path` made absolute with `..` and symlinks resolved, as far as it exists;
+/// the rest, which doesn't exist yet, is resolved as written
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            std::path::Component::CurDir => {}
+            component => resolved.push(component),
+        }
+        if resolved.exists() {
+            resolved = resolved.canonicalize()?;
+        }
+    }
+    Ok(resolved)
+}
+
+/// Back up the notes directory into `out_dir` and print the archive's path and size
+fn backup_notes(config: &Config, out_dir: &Path, dry_run: bool) -> io::Result<()> {
+    let target = resolve_path(out_dir)?.join(backup_file_name(&config.now()?));
+    if target.starts_with(resolve_path(&config.notes_dir)?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "refusing to write the backup inside the notes directory",
+        ));
+    }
+    if dry_run {
+        println!(
+            "Would back up {} to {}",
+            config.notes_dir.display(),
+            target.display()
+        );
+        return Ok(());
+    }
+    fs::create_dir_all(out_dir)?;
+    write_backup(&config.notes_dir, &target)?;
+    println!(
+        "{} ({})",
+        target.display(),
+        format_size(fs::metadata(&target)?.len())
+    );
+    Ok(())
+}
+
+/// Whether there is a note for `date`, plain or encrypted
+fn note_exists(config: &Config, date: Date) -> bool {
+    let path = get_note_path(config, date);
+    path.exists() || encrypted_path(&path).exists()
+}
+
+/// Where the encrypted form of the note at `path` is kept, `<date>.md.age`
+fn encrypted_path(path: &Path) -> PathBuf {
+    let mut encrypted = path.as_os_str().to_owned();
+    encrypted.push(".age");
+    PathBuf::from(encrypted)
+}
+
+/// Whether `path` is an encrypted note rather than a plain one
+fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "age")
+}
+
+/// The file holding the note for `date`: the plain note if there is one,
+/// otherwise its encrypted form, or `None` if neither exists
+fn existing_note_path(config: &Config, date: Date) -> Option<PathBuf> {
+    let path = get_note_path(config, date);
+    if path.exists() {
+        return Some(path);
+    }
+    Some(encrypted_path(&path)).filter(|encrypted| encrypted.exists())
+}
+
+/// The error for a command that needs to read or write the plain text of a
+/// note that is only stored encrypted
+fn note_encrypted(date: Date, hint: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("the note for {} is encrypted, {}", date, hint),
+    )
+}
+
+/// Read a passphrase from `NN_PASSPHRASE` or the terminal, asking twice when
+/// setting a new one
+fn read_passphrase(new: bool) -> io::Result<SecretString> {
+    if let Ok(passphrase) = env::var("NN_PASSPHRASE") {
+        return Ok(passphrase.into());
+    }
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if new && rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "passphrases do not match",
+        ));
+    }
+    Ok(passphrase.into())
+}
+
+/// Encrypt note contents to an age file for the given passphrase recipient
+fn encrypt_contents(plaintext: &[u8], recipient: &age::scrypt::Recipient) -> io::Result<Vec<u8>> {
+    let encryptor = age::Encryptor::with_recipients(std::iter::once(recipient as _))
+        .map_err(io::Error::other)?;
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut encrypted)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+    Ok(encrypted)
+}
+
+/// Decrypt an age file with a passphrase
+fn decrypt_contents(encrypted: &[u8], passphrase: SecretString) -> io::Result<String> {
+    let invalid = |e: age::DecryptError| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("could not decrypt note: {}", e),
+        )
+    };
+    let decryptor = age::Decryptor::new(encrypted).map_err(invalid)?;
+    let identity = age::scrypt::Identity::new(passphrase);
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as _))
+        .map_err(invalid)?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Edit the note for `date` through a temporary plaintext copy and store it
+/// encrypted as `<date>.md.age`, replacing any unencrypted note
+fn edit_encrypted_note(config: &Config, date: Date, dry_run: bool) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    let encrypted = encrypted_path(&path);
+    if dry_run {
+        let editor = resolve_editor(config);
+        println!(
+            "Would open {} in {} and encrypt it to {}",
+            path.display(),
+            editor.join(" "),
+            encrypted.display()
+        );
+        return Ok(());
+    }
+
+    let (passphrase, contents) = if encrypted.exists() {
+        let passphrase = read_passphrase(false)?;
+        let contents = decrypt_contents(&fs::read(&encrypted)?, passphrase.clone())?;
+        (passphrase, contents)
+    } else if path.exists() {
+        (read_passphrase(true)?, fs::read_to_string(&path)?)
+    } else {
+        (
+            read_passphrase(true)?,
+            new_note_contents(config, &path, date),
+        )
+    };
+
+    // the plaintext only lives in a private temporary file while editing
+    let mut scratch = tempfile::Builder::new()
+        .suffix(&format!(".{}", config.note_extension))
+        .tempfile()?;
+    scratch.write_all(contents.as_bytes())?;
+    scratch.flush()?;
+    open_editor(scratch.path(), &resolve_editor(config))?;
+    let edited = fs::read(scratch.path())?;
+
+    if let Some(parent) = encrypted.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let recipient = age::scrypt::Recipient::new(passphrase);
+    fs::write(&encrypted, encrypt_contents(&edited, &recipient)?)?;
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Recursively collect every file with the given `extension` below `dir` into
+/// `paths`, skipping hidden files and directories, the `exclude` directories
+/// and anything whose name matches one of the `ignore` globs
+fn walk_dir(
+    dir: &Path,
+    extension: &str,
+    exclude: &[PathBuf],
+    ignore: &[Regex],
+    paths: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            verbose!(2, "skipping hidden {}", path.display());
+        } else if ignore.iter().any(|glob| glob.is_match(&name)) {
+            verbose!(2, "ignoring {}", path.display());
+        } else if path.is_dir() {
+            if exclude.contains(&path) {
+                verbose!(2, "skipping {}", path.display());
+            } else {
+                walk_dir(&path, extension, exclude, ignore, paths)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            verbose!(2, "found note {}", path.display());
+            paths.push(path);
+        } else {
+            verbose!(2, "skipping {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Collect the paths of all notes in the configured notes directory,
+/// including those in subdirectories but not, unless asked, the archive
+fn note_paths(config: &Config) -> io::Result<Vec<PathBuf>> {
+    walk_notes(config, &config.note_extension)
+}
+
+/// Collect the paths of all encrypted notes, `<note>.md.age`, which
+/// [`note_paths`] leaves out since they can't be read without a passphrase
+fn encrypted_note_paths(config: &Config) -> io::Result<Vec<PathBuf>> {
+    let mut paths = walk_notes(config, "age")?;
+    paths.retain(|path| {
+        path.file_stem()
+            .map(Path::new)
+            .and_then(Path::extension)
+            .is_some_and(|ext| *ext == *config.note_extension)
+    });
+    Ok(paths)
+}
+
+/// Walk the notes directory for files with `extension`, as [`note_paths`] does
+fn walk_notes(config: &Config, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut exclude = vec![config.notes_dir.join("attachments")];
+    if !config.include_archived {
+        exclude.push(config.archive_dir());
+    }
+    let ignore = config
+        .ignore
+        .iter()
+        .map(|glob| glob_regex(glob))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    walk_dir(&config.notes_dir, extension, &exclude, &ignore, &mut paths)?;
+    Ok(paths)
+}
+
+/// Read a note found while walking the notes directory. Invalid UTF-8 is
+/// replaced with a warning so the rest of the note still counts; a note that
+/// cannot be read at all is reported and skipped, or with `--strict` is an error.
+fn read_walked_note(config: &Config, path: &Path) -> io::Result<Option<String>> {
+    match fs::read(path) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) => {
+                eprintln!(
+                    "Warning: {} is not valid UTF-8, invalid bytes were replaced",
+                    path.display()
+                );
+                Ok(Some(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+            }
+        },
+        Err(e) if config.strict => Err(io::Error::new(
+            e.kind(),
+            format!("could not read {}: {}", path.display(), e),
+        )),
+        Err(e) => {
+            eprintln!("Warning: skipping {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// The single undated scratch note, `scratch.md` in the notes directory
+fn scratch_path(config: &Config) -> PathBuf {
+    config
+        .notes_dir
+        .join(format!("scratch.{}", config.note_extension))
+}
+
+/// The generated table of contents, `index.md` in the notes directory
+fn index_path(config: &Config) -> PathBuf {
+    config.notes_dir.join("index.md")
+}
+
+/// The notes counted by `list` and `stats`, which leave out the scratch note
+/// and the generated index
+fn listed_note_paths(config: &Config) -> io::Result<Vec<PathBuf>> {
+    let (scratch, index) = (scratch_path(config), index_path(config));
+    let mut paths = note_paths(config)?;
+    paths.retain(|path| *path != scratch && *path != index);
+    Ok(paths)
+}
+
+/// Open the scratch note in the editor, creating it with a `# Scratch`
+/// heading if needed
+fn edit_scratch(config: &Config, dry_run: bool) -> io::Result<()> {
+    let path = scratch_path(config);
+    if dry_run {
+        if !path.exists() {
+            println!("Would create {}", path.display());
+        }
+        println!(
+            "Would open {} in {}",
+            path.display(),
+            resolve_editor(config).join(" ")
+        );
+        return Ok(());
+    }
+    if !path.exists() {
+        fs::create_dir_all(&config.notes_dir)?;
+        fs::write(&path, format!("{}Scratch\n\n", heading_marker(&path)))?;
+    }
+    open_editor(&path, &resolve_editor(config))?;
+    run_after_edit(config, &path)
+}
+
+/// Name a note by its path relative to the notes directory, without the
+/// extension and with `/` separators, e.g. `2025-04-09` or `2025/04/09`
+fn note_name(config: &Config, path: &Path) -> String {
+    let relative = path.strip_prefix(&config.notes_dir).unwrap_or(path);
+    let relative = if is_encrypted(relative) {
+        relative.with_extension("")
+    } else {
+        relative.to_path_buf()
+    };
+    relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Get the date a note is filed under from its path, if it matches the date format
+fn note_date(config: &Config, path: &Path) -> Option<Date> {
+    Date::strptime(&config.date_format, note_name(config, path)).ok()
+}
+
+/// Sort note paths by their date, newest first unless `oldest_first` is set.
+/// Notes without a date go last, ordered by name.
+fn sort_notes(config: &Config, paths: &mut [PathBuf], oldest_first: bool) {
+    paths.sort_by(|a, b| match (note_date(config, a), note_date(config, b)) {
+        (Some(x), Some(y)) if oldest_first => x.cmp(&y),
+        (Some(x), Some(y)) => y.cmp(&x),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => note_name(config, a).cmp(&note_name(config, b)),
+    });
+}
+
+/// Metadata about a note, as emitted by `list --json`
+#[derive(Debug, Serialize)]
+struct NoteEntry {
+    /// The date parsed from the filename, if it matches the date format
+    date: Option<String>,
+    path: PathBuf,
+    size_bytes: u64,
+    /// Last modification time as an RFC 3339 timestamp
+    modified: Option<String>,
+    pinned: bool,
+}
+
+impl NoteEntry {
+    /// Gather metadata for the note at the given path
+    fn from_path(config: &Config, path: PathBuf) -> io::Result<Self> {
+        let metadata = fs::metadata(&path)?;
+        let date = note_date(config, &path).map(|d| d.to_string());
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| jiff::Timestamp::try_from(time).ok())
+            .map(|ts| ts.to_string());
+        Ok(NoteEntry {
+            date,
+            path,
+            size_bytes: metadata.len(),
+            modified,
+            pinned: false,
+        })
+    }
+}
+
+/// Print a list of all notes in the configured notes directory, by date or
+/// path, marking pinned notes
+fn list_notes(
+    config: &Config,
+    pins: &Pins,
+    json: bool,
+    oldest_first: bool,
+    full_paths: bool,
+    range: DateRange,
+    limit: Option<usize>,
+) -> io::Result<()> {
+    let mut paths = listed_note_paths(config)?;
+    paths.extend(encrypted_note_paths(config)?);
+    range.retain(config, &mut paths);
+    sort_notes(config, &mut paths, oldest_first);
+    let total = paths.len();
+    paths.truncate(limit.unwrap_or(total));
+    let hidden = total - paths.len();
+
+    if json {
+        let notes = paths
+            .into_iter()
+            .map(|path| {
+                let mut entry = NoteEntry::from_path(config, path)?;
+                entry.pinned = pins.notes.contains(&note_name(config, &entry.path));
+                Ok(entry)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&notes).map_err(io::Error::other)?
+        );
+        return Ok(());
+    }
+    for path in paths {
+        if config.porcelain {
+            let name = note_name(config, &path);
+            let pinned = pins.notes.contains(&name);
+            println!("{}\t{}\t{}", name, path.display(), pinned);
+        } else if full_paths {
+            println!("{}", path.display());
+        } else {
+            let name = note_name(config, &path);
+            if pins.notes.contains(&name) {
+                println!("{} (pinned)", name);
+            } else {
+                println!("{}", name);
+            }
+        }
+    }
+    if let Some(footer) = more_footer(hidden).filter(|_| !config.porcelain) {
+        println!("{}", footer);
+    }
+    Ok(())
+}
+
+/// Pinned notes, stored by name in `pins.toml` beside the config file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Pins {
+    #[serde(default)]
+    notes: BTreeSet<String>,
+}
+
+impl Pins {
+    /// The pin store inside the given config directory
+    fn path(config_dir: &Path) -> PathBuf {
+        config_dir.join("pins.toml")
+    }
+
+    /// Load the pin store, which is empty if it has not been written yet
+    fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(toml_str) => toml::from_str(&toml_str).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid pins file {}: {}", path.display(), e),
+                )
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Pins::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, toml::to_string(self).map_err(io::Error::other)?)
+    }
+}
+
+/// Pin or unpin the note for `date` in the pin store at `pins_path`
+fn set_pinned(
+    config: &Config,
+    pins_path: &Path,
+    date: Date,
+    pinned: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    let name = note_name(config, &path);
+    let mut pins = Pins::load(pins_path)?;
+    if pinned {
+        if !note_exists(config, date) {
+            return Err(note_not_found(date));
+        }
+        if dry_run {
+            println!("Would pin {}", name);
+            return Ok(());
+        }
+        if pins.notes.insert(name.clone()) {
+            pins.save(pins_path)?;
+        }
+        println!("Pinned {}", name);
+    } else if !pins.notes.contains(&name) {
+        println!("{} is not pinned", name);
+    } else if dry_run {
+        println!("Would unpin {}", name);
+    } else {
+        pins.notes.remove(&name);
+        pins.save(pins_path)?;
+        println!("Unpinned {}", name);
+    }
+    Ok(())
+}
+
+/// Read the note for the given date, decrypting it if it is only stored
+/// encrypted, with a clear error if there is none
+fn read_note(config: &Config, date: Date) -> io::Result<String> {
+    let path = get_note_path(config, date);
+    let encrypted = encrypted_path(&path);
+    if !path.exists() && encrypted.exists() {
+        return decrypt_contents(&fs::read(&encrypted)?, read_passphrase(false)?);
+    }
+    fs::read_to_string(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => note_not_found(date),
+        _ => io::Error::new(
+            e.kind(),
+            format!("could not read {}: {}", path.display(), e),
+        ),
+    })
+}
+
+/// Print the note for the given date to stdout, raw or rendered for the terminal
+fn show_note(config: &Config, date: Date, render: bool) -> io::Result<()> {
+    let contents = read_note(config, date)?;
+    if render {
+        print!(
+            "{}",
+            render_markdown_ansi(&contents, config.color.enabled(io::stdout().is_terminal()))
+        );
+    } else {
+        print!("{}", contents);
+    }
+    Ok(())
+}
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const GREEN: &str = "32";
+const CYAN: &str = "36";
+const MAGENTA: &str = "35";
+const YELLOW: &str = "33";
+const RED: &str = "31";
+
+/// Wrap text in an ANSI SGR sequence when `color` is set
+fn paint(text: &str, sgr: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Style `**bold**` and `` `code` `` spans within a line of markdown
+fn render_inline_ansi(line: &str, color: bool) -> String {
+    static INLINE_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\*\*([^*]+)\*\*|`([^`]+)`").unwrap());
+    INLINE_RE
+        .replace_all(line, |caps: &regex::Captures| {
+            match (caps.get(1), caps.get(2)) {
+                (Some(bold), _) => paint(bold.as_str(), BOLD, color),
+                (_, Some(code)) => paint(code.as_str(), CYAN, color),
+                _ => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Pretty-print markdown for the terminal: headings are bold, list markers
+/// become bullets and fenced code is indented, with ANSI colors if `color`
+fn render_markdown_ansi(markdown: &str, color: bool) -> String {
+    let mut out = String::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        let rendered = if in_code {
+            format!("    {}", paint(line, CYAN, color))
+        } else if is_heading(line) {
+            let text = line.trim_start_matches('#').trim();
+            let sgr = if line.starts_with("# ") {
+                format!("{};{}", BOLD, MAGENTA)
+            } else {
+                format!("{};{}", BOLD, YELLOW)
+            };
+            paint(text, &sgr, color)
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            let indent = &line[..line.len() - trimmed.len()];
+            format!(
+                "{}{} {}",
+                indent,
+                paint("•", DIM, color),
+                render_inline_ansi(item, color)
+            )
+        } else {
+            render_inline_ansi(line, color)
+        };
+        out.push_str(&rendered);
+        out.push('\n');
+    }
+    out
+}
+
+/// Describe an elapsed number of seconds in words, e.g. "3 hours ago"
+fn format_ago(seconds: u64) -> String {
+    let (n, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if n == 1 { "" } else { "s" };
+    format!("{} {}{} ago", n, unit, plural)
+}
+
+/// Print the `count` most recently modified notes with how long ago they changed
+fn recent_notes(config: &Config, count: usize) -> io::Result<()> {
+    let mut notes = note_paths(config)?
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)?.modified()?;
+            Ok((path, modified))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    notes.sort_by_key(|&(_, modified)| Reverse(modified));
+
+    let now = SystemTime::now();
+    let hidden = notes.len().saturating_sub(count);
+    for (path, modified) in notes.into_iter().take(count) {
+        if config.porcelain {
+            let modified = Timestamp::try_from(modified).map_err(io::Error::other)?;
+            println!("{}\t{}", note_name(config, &path), modified);
+            continue;
+        }
+        let elapsed = now.duration_since(modified).unwrap_or_default();
+        println!(
+            "{}  modified {}",
+            note_name(config, &path),
+            format_ago(elapsed.as_secs())
+        );
+    }
+    if let Some(footer) = more_footer(hidden).filter(|_| !config.porcelain) {
+        println!("{}", footer);
+    }
+    Ok(())
+}
+
+/// The footer printed after output cut short by `--limit`, if anything was left out
+fn more_footer(hidden: usize) -> Option<String> {
+    (hidden > 0).then(|| format!("... and {} more", hidden))
+}
+
+/// Ask a yes/no question on stdin, treating anything but `y` as no
+fn confirm(prompt: &str) -> io::Result<bool> {
+    // prompt on stderr so it never ends up in redirected output
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Find a free path for `relative` inside `dir`, appending a numeric suffix to
+/// the file stem (`2025-04-09-1.md`) if it is already taken
+fn unique_path(dir: &Path, relative: &Path) -> PathBuf {
+    let target = dir.join(relative);
+    if !target.exists() {
+        return target;
+    }
+    // keep an encrypted note's `.md.age` together after the suffix
+    let plain = if is_encrypted(&target) {
+        target.with_extension("")
+    } else {
+        target.clone()
+    };
+    let stem = plain
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut extension = plain
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    if plain != target {
+        extension.push_str(".age");
+    }
+    (1..)
+        .map(|n| target.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Where a note would be archived to, keeping its path relative to the notes directory
+fn archive_target(config: &Config, path: &Path) -> PathBuf {
+    let relative = path.strip_prefix(&config.notes_dir).unwrap_or(path);
+    unique_path(&config.archive_dir(), relative)
+}
+
+/// Move a note into the archive directory and return where it ended up
+fn archive_note(config: &Config, path: &Path) -> io::Result<PathBuf> {
+    let target = archive_target(config, path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+/// Delete the note file corresponding to the given date, if it exists, and
+/// its attachments with `with_attachments`. Asks for confirmation first unless
+/// `yes` is set; with `archive` the note is moved to the archive directory
+/// instead, which needs no confirmation.
+fn delete_note(
+    config: &Config,
+    date: Date,
+    yes: bool,
+    archive: bool,
+    with_attachments: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    let Some(path) = existing_note_path(config, date) else {
+        return Err(note_not_found(date));
+    };
+    if dry_run {
+        if archive {
+            let target = archive_target(config, &path);
+            println!("Would archive {} to {}", path.display(), target.display());
+        } else {
+            println!("Would delete {}", path.display());
+            if with_attachments {
+                delete_attachments(config, &path, true)?;
+            }
+        }
+    } else if archive {
+        let target = archive_note(config, &path)?;
+        println!("Archived note for {} to {}", date, target.display());
+    } else {
+        if !yes {
+            if !io::stdin().is_terminal() {
+                return Err(io::Error::other(
+                    "refusing to delete without confirmation, pass --yes to skip the prompt",
+                ));
+            }
+            if !confirm(&format!("Delete {}?", path.display()))? {
+                println!("Kept note for {}", date);
+                return Ok(());
+            }
+        }
+        fs::remove_file(&path)?;
+        println!("Deleted note for {}", date);
+        if with_attachments {
+            delete_attachments(config, &path, false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a delete argument is a glob pattern rather than a single date
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Compile a shell-style glob (`*`, `?` and `[...]` classes) into an anchored regex
+fn glob_regex(pattern: &str) -> Result<Regex, String> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                let invalid = |reason: &str| format!("invalid glob '{}': {}", pattern, reason);
+                let start = re.len();
+                re.push('[');
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => {
+                            closed = true;
+                            break;
+                        }
+                        '!' if re.len() == start + 1 => re.push('^'),
+                        '\\' | '^' | '[' | '&' | '~' => {
+                            re.push('\\');
+                            re.push(c);
+                        }
+                        _ => re.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(invalid("unterminated '[' character class"));
+                }
+                if re.len() == start + 1 || &re[start..] == "[^" {
+                    return Err(invalid("empty '[]' character class"));
+                }
+                re.push(']');
+                Regex::new(&re[start..]).map_err(|_| invalid("malformed character class"))?;
+            }
+            _ => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| format!("invalid glob '{}': {}", pattern, e))
+}
+
+/// The notes whose names match `glob` (when given) and whose dates fall in
+/// `range`, oldest first
+fn matching_notes(
+    config: &Config,
+    glob: Option<&Regex>,
+    range: DateRange,
+) -> io::Result<Vec<PathBuf>> {
+    let mut paths = listed_note_paths(config)?;
+    if let Some(glob) = glob {
+        paths.retain(|path| glob.is_match(&note_name(config, path)));
+    }
+    range.retain(config, &mut paths);
+    sort_notes(config, &mut paths, true);
+    Ok(paths)
+}
+
+/// Delete (or with `archive`, archive) several notes at once, along with their
+/// attachments if `with_attachments` is set. The notes are always listed first
+/// and deleting them needs confirmation unless `yes` is set.
+fn delete_notes(
+    config: &Config,
+    paths: &[PathBuf],
+    yes: bool,
+    archive: bool,
+    with_attachments: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    if paths.is_empty() {
+        eprintln!("No notes match");
+        return Ok(());
+    }
+    let verb = if archive { "archive" } else { "delete" };
+    println!("{} notes to {}:", paths.len(), verb);
+    for path in paths {
+        println!("  {}", path.display());
+    }
+    if dry_run {
+        println!("Would {} {} notes", verb, paths.len());
+        if with_attachments {
+            for path in paths {
+                delete_attachments(config, path, true)?;
+            }
+        }
+        return Ok(());
+    }
+    if !archive && !yes {
+        if !io::stdin().is_terminal() {
+            return Err(io::Error::other(
+                "refusing to delete without confirmation, pass --yes to skip the prompt",
+            ));
+        }
+        if !confirm(&format!("Delete these {} notes?", paths.len()))? {
+            println!("Kept all notes");
+            return Ok(());
+        }
+    }
+    for path in paths {
+        if archive {
+            archive_note(config, path)?;
+        } else {
+            fs::remove_file(path)?;
+            if with_attachments {
+                delete_attachments(config, path, false)?;
+            }
+        }
+    }
+    let done = if archive { "Archived" } else { "Deleted" };
+    println!("{} {} notes", done, paths.len());
+    Ok(())
+}
+
+/// Whether a note holds nothing but what `nn` would have generated for it: a
+/// heading naming the note (or its date per `heading_format`) and possibly
+/// frontmatter with no tags
+fn is_empty_note(config: &Config, path: &Path, contents: &str) -> bool {
+    let mut body = contents;
+    if let Some(frontmatter) = parse_frontmatter(contents) {
+        let generated = frontmatter.tags.is_empty()
+            && frontmatter
+                .fields
+                .iter()
+                .all(|(key, _)| key == "date" || key == "tags");
+        if !generated {
+            return false;
+        }
+        // skip past the closing `---`
+        body = contents.splitn(3, "---").nth(2).unwrap_or_default();
+    }
+    // older notes were headed with their file name, extension included
+    let mut titles = vec![
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        note_name(config, path),
+    ];
+    titles.extend(note_date(config, path).map(|date| note_heading(config, path, date)));
+    let marker = heading_marker(path);
+    let mut lines = body.lines().map(str::trim).filter(|line| !line.is_empty());
+    match lines.next() {
+        None => true,
+        Some(line) => {
+            let title = line.strip_prefix(marker).map(str::trim);
+            title.is_some_and(|title| titles.iter().any(|t| t == title)) && lines.next().is_none()
+        }
+    }
+}
+
+/// Delete every note that is still an empty stub, listing them first
+fn clean_notes(config: &Config, yes: bool, dry_run: bool) -> io::Result<()> {
+    let mut empty = Vec::new();
+    for path in listed_note_paths(config)? {
+        if read_walked_note(config, &path)?
+            .is_some_and(|contents| is_empty_note(config, &path, &contents))
+        {
+            empty.push(path);
+        }
+    }
+    let mut paths = empty;
+    if paths.is_empty() {
+        println!("No empty notes");
+        return Ok(());
+    }
+    sort_notes(config, &mut paths, true);
+    delete_notes(config, &paths, yes, false, false, dry_run)
+}
+
+/// Rewrite the first heading (starting with `marker`) of a note if it names
+/// the note's old file, as `# 2025-04-09` or `# 2025-04-09.md`, returning
+/// `None` if it does not
+fn retitle_heading(contents: &str, marker: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let heading = contents.lines().find(|line| line.starts_with(marker))?;
+    let title = heading[marker.len()..].trim();
+    let new_title = if title == old_name {
+        new_name.to_string()
+    } else {
+        match title.rsplit_once('.') {
+            Some((stem, extension)) if stem == old_name => format!("{}.{}", new_name, extension),
+            _ => return None,
+        }
+    };
+    let start = heading.as_ptr() as usize - contents.as_ptr() as usize;
+    Some(format!(
+        "{}{}{}{}",
+        &contents[..start],
+        marker,
+        new_title,
+        &contents[start + heading.len()..]
+    ))
+}
+
+/// Move the note for `from` to `to`, refusing to overwrite an existing note
+/// unless `force`, and offering to update a heading that names the old date
+fn move_note(
+    config: &Config,
+    from: Date,
+    to: Date,
+    force: bool,
+    yes: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    let Some(source) = existing_note_path(config, from) else {
+        return Err(note_not_found(from));
+    };
+    // an encrypted note stays encrypted, and may not end up beside a plain one
+    let encrypted = is_encrypted(&source);
+    let plain = get_note_path(config, to);
+    let (target, other) = if encrypted {
+        (encrypted_path(&plain), plain)
+    } else {
+        (plain.clone(), encrypted_path(&plain))
+    };
+    if other.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "a note for {} already exists as {}, move or delete it first",
+                to,
+                other.display()
+            ),
+        ));
+    }
+    if target.exists() && !force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "a note for {} already exists, pass --force to overwrite it",
+                to
+            ),
+        ));
+    }
+    if dry_run {
+        println!("Would move {} to {}", source.display(), target.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&source, &target)?;
+    println!("Moved note for {} to {}", from, to);
+    if encrypted {
+        return Ok(());
+    }
+
+    let contents = fs::read_to_string(&target)?;
+    let (old_name, new_name) = (note_name(config, &source), note_name(config, &target));
+    let marker = heading_marker(&target);
+    let updated = retitle_heading(&contents, marker, &old_name, &new_name).or_else(|| {
+        let old_heading = note_heading(config, &source, from);
+        retitle_heading(
+            &contents,
+            marker,
+            &old_heading,
+            &note_heading(config, &target, to),
+        )
+    });
+    if let Some(updated) = updated {
+        let update = yes
+            || (io::stdin().is_terminal() && confirm(&format!("Update heading to {}?", new_name))?);
+        if update {
+            fs::write(&target, updated)?;
+            println!("Updated heading to {}", new_name);
+        }
+    }
+    Ok(())
+}
+
+/// The notes other than the canonical one that belong to `date`: those whose
+/// name parses with the date format, or whose file name starts with the ISO
+/// date, such as `2025-04-09-1.md` or `2025-04-09 imported.md`
+fn same_day_notes(config: &Config, date: Date) -> io::Result<Vec<PathBuf>> {
+    static DATED_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\d{4}-\d{2}-\d{2})(?:[-_ .].*)?$").unwrap());
+    let canonical = get_note_path(config, date);
+    let mut paths: Vec<PathBuf> = note_paths(config)?
+        .into_iter()
+        .filter(|path| *path != canonical)
+        .filter(|path| {
+            let stem = path.file_stem().and_then(|stem| stem.to_str());
+            note_date(config, path) == Some(date)
+                || stem
+                    .and_then(|stem| DATED_RE.captures(stem))
+                    .is_some_and(|caps| parse_date(&caps[1]) == Ok(date))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Merge the other files for `date` into its canonical note, each under a
+/// `## <name>` heading in path order, deleting them only if `force`
+fn merge_notes(config: &Config, date: Date, force: bool, dry_run: bool) -> io::Result<()> {
+    let canonical = get_note_path(config, date);
+    if !canonical.exists() && encrypted_path(&canonical).exists() {
+        return Err(note_encrypted(
+            date,
+            "so other notes can't be merged into it",
+        ));
+    }
+    let sources = same_day_notes(config, date)?;
+    if sources.is_empty() {
+        println!("Nothing to merge for {}", date);
+        return Ok(());
+    }
+    if dry_run {
+        for source in &sources {
+            println!(
+                "Would merge {} into {}",
+                source.display(),
+                canonical.display()
+            );
+        }
+        return Ok(());
+    }
+
+    // a canonical note that exists but can't be read must not be overwritten
+    let mut merged = match fs::read_to_string(&canonical) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("could not read {}: {}", canonical.display(), e),
+            ))
+        }
+    };
+    for source in &sources {
+        let contents = fs::read_to_string(source)?;
+        if !merged.is_empty() {
+            merged.push_str(if merged.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+        merged.push_str(&format!("## {}\n\n{}", note_name(config, source), contents));
+        println!("Merged {}", source.display());
+    }
+    if let Some(parent) = canonical.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&canonical, merged)?;
+
+    if force {
+        for source in &sources {
+            fs::remove_file(source)?;
+        }
+        println!("Deleted {} merged files", sources.len());
+    } else {
+        eprintln!(
+            "Warning: kept {} merged files, pass --force to delete them",
+            sources.len()
+        );
+    }
+    Ok(())
+}
+
+/// How a search query is matched against lines of a note
+enum Matcher {
+    /// Plain substring match; the query is stored lowercase when ignoring case
+    Literal {
+        query: String,
+        case_insensitive: bool,
+    },
+    /// Regular expression match
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Build a matcher from the search options, compiling the query if it is a regex
+    fn new(query: &str, case_insensitive: bool, regex: bool) -> Result<Self, regex::Error> {
+        if regex {
+            let re = RegexBuilder::new(query)
+                .case_insensitive(case_insensitive)
+                .build()?;
+            Ok(Matcher::Regex(re))
+        } else {
+            let query = if case_insensitive {
+                query.to_lowercase()
+            } else {
+                query.to_string()
+            };
+            Ok(Matcher::Literal {
+                query,
+                case_insensitive,
+            })
+        }
+    }
+
+    /// Check whether a line matches the query
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Literal {
+                query,
+                case_insensitive: true,
+            } => line.to_lowercase().contains(query.as_str()),
+            Matcher::Literal { query, .. } => line.contains(query.as_str()),
+            Matcher::Regex(re) => re.is_match(line),
+        }
+    }
+
+    /// Byte ranges of the matches within a line
+    fn spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let literal = |haystack: &str, query: &str| {
+            if query.is_empty() {
+                return Vec::new();
+            }
+            haystack
+                .match_indices(query)
+                .map(|(start, m)| (start, start + m.len()))
+                .collect()
+        };
+        match self {
+            Matcher::Literal {
+                query,
+                case_insensitive: true,
+            } => {
+                let lower = line.to_lowercase();
+                // offsets into the lowercased line only line up if lowercasing
+                // kept every character the same width
+                if lower.len() == line.len() {
+                    literal(&lower, query)
+                } else {
+                    Vec::new()
+                }
+            }
+            Matcher::Literal { query, .. } => literal(line, query),
+            Matcher::Regex(re) => re
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+        }
+    }
+
+    /// The line with each match painted bold red when `color` is set
+    fn highlight(&self, line: &str, color: bool) -> String {
+        if !color {
+            return line.to_string();
+        }
+        let (mut out, mut last) = (String::new(), 0);
+        for (start, end) in self.spans(line) {
+            out.push_str(&line[last..start]);
+            out.push_str(&paint(
+                &line[start..end],
+                &format!("{};{}", BOLD, RED),
+                true,
+            ));
+            last = end;
+        }
+        out.push_str(&line[last..]);
+        out
+    }
+}
+
+/// Merge the windows of `context` lines around each matching line index into
+/// sorted, non-overlapping inclusive ranges bounded by `line_count`
+fn context_ranges(matches: &[usize], context: usize, line_count: usize) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &m in matches {
+        let start = m.saturating_sub(context);
+        let end = (m + context).min(line_count.saturating_sub(1));
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+/// Where search results go: the `--output` file if given, otherwise stdout
+fn output_writer(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    match path {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("could not create {}: {}", path.display(), e),
+                )
+            })?;
+            Ok(Box::new(io::BufWriter::new(file)))
+        }
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+/// What `search_notes` writes for the notes that match
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SearchMode {
+    /// The note's path and its matching lines, with this many lines of context
+    Lines(usize),
+    /// Only the note's path
+    Files,
+    /// Nothing, stopping at the first match
+    Quiet,
+}
+
+/// Search all notes line by line and write each matching note's path followed
+/// by its matching lines, or less depending on `mode`, to `out`, newest first
+/// and stopping after `limit` notes. Matches are highlighted if `color` is
+/// set. Returns the number of matching lines, which is just 1 for
+/// `SearchMode::Quiet` if anything matched.
+fn search_notes(
+    config: &Config,
+    matcher: &Matcher,
+    mode: SearchMode,
+    range: DateRange,
+    limit: Option<usize>,
+    color: bool,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut paths = note_paths(config)?;
+    range.retain(config, &mut paths);
+    sort_notes(config, &mut paths, false);
+    let (mut total, mut notes) = (0, 0);
+    for path in paths {
+        if let Some(contents) = read_walked_note(config, &path)? {
+            let lines: Vec<&str> = contents.lines().collect();
+            let matches: Vec<usize> = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| matcher.is_match(line))
+                .map(|(i, _)| i)
+                .collect();
+            if matches.is_empty() {
+                continue;
+            }
+            if mode == SearchMode::Quiet {
+                return Ok(1);
+            }
+            total += matches.len();
+            notes += 1;
+            if limit.is_some_and(|limit| notes > limit) {
+                continue;
+            }
+            let SearchMode::Lines(context) = mode else {
+                writeln!(
+                    out,
+                    "{}",
+                    paint(&path.display().to_string(), MAGENTA, color)
+                )?;
+                continue;
+            };
+            if config.porcelain {
+                for &i in &matches {
+                    writeln!(out, "{}\t{}\t{}", path.display(), i + 1, lines[i])?;
+                }
+                continue;
+            }
+
+            writeln!(
+                out,
+                "{}:",
+                paint(&path.display().to_string(), MAGENTA, color)
+            )?;
+            for (n, (start, end)) in context_ranges(&matches, context, lines.len())
+                .into_iter()
+                .enumerate()
+            {
+                if n > 0 {
+                    writeln!(out, "{}", paint("--", CYAN, color))?;
+                }
+                for (i, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+                    // `:` marks a matching line and `-` a context line, as grep does
+                    let is_match = matches.binary_search(&i).is_ok();
+                    let (sep, line) = if is_match {
+                        (":", matcher.highlight(line, color))
+                    } else {
+                        ("-", line.to_string())
+                    };
+                    writeln!(
+                        out,
+                        "{}{}{}",
+                        paint(&(i + 1).to_string(), GREEN, color),
+                        paint(sep, CYAN, color),
+                        line
+                    )?;
+                }
+            }
+        }
+    }
+    let hidden = notes.saturating_sub(limit.unwrap_or(notes));
+    if let Some(footer) = more_footer(hidden).filter(|_| !config.porcelain) {
+        writeln!(out, "{}", footer)?;
+    }
+    out.flush()?;
+    Ok(total)
+}
+
+/// Write `path: N` for up to `limit` notes with matching lines, then the grand
+/// total, like `grep -c`. Returns the total number of matching lines.
+fn count_matches(
+    config: &Config,
+    matcher: &Matcher,
+    range: DateRange,
+    limit: Option<usize>,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut paths = note_paths(config)?;
+    range.retain(config, &mut paths);
+    sort_notes(config, &mut paths, false);
+    let (mut total, mut notes) = (0, 0);
+    for path in paths {
+        if let Some(contents) = read_walked_note(config, &path)? {
+            let n = contents
+                .lines()
+                .filter(|line| matcher.is_match(line))
+                .count();
+            if n > 0 {
+                total += n;
+                notes += 1;
+                if limit.is_none_or(|limit| notes <= limit) {
+                    if config.porcelain {
+                        writeln!(out, "{}\t{}", path.display(), n)?;
+                    } else {
+                        writeln!(out, "{}: {}", path.display(), n)?;
+                    }
+                }
+            }
+        }
+    }
+    if config.porcelain {
+        out.flush()?;
+        return Ok(total);
+    }
+    if let Some(footer) = more_footer(notes.saturating_sub(limit.unwrap_or(notes))) {
+        writeln!(out, "{}", footer)?;
+    }
+    if total > 0 {
+        writeln!(out, "total: {}", total)?;
+    }
+    out.flush()?;
+    Ok(total)
+}
+
+/// Matches wikilinks such as `[[2025-04-01]]`, `[[projects/nn|the nn note]]`
+/// or `[[2025-04-01#Plans]]`, capturing the target note name
+static WIKILINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[([^\[\]|#]+)(?:[|#][^\[\]]*)?\]\]").unwrap());
+
+/// Find the target of every wikilink in the given text, in order of appearance
+fn find_links(contents: &str) -> impl Iterator<Item = &str> {
+    WIKILINK_RE
+        .captures_iter(contents)
+        .map(|caps| caps.get(1).unwrap().as_str().trim())
+}
+
+/// The file a wikilink target refers to, relative to the notes directory
+fn link_target_path(config: &Config, target: &str) -> PathBuf {
+    config
+        .notes_dir
+        .join(format!("{}.{}", target, config.note_extension))
+}
+
+/// List the distinct link targets of the note for `date`, marking those
+/// without a note as missing
+fn show_links(config: &Config, date: Date) -> io::Result<()> {
+    let contents = read_note(config, date)?;
+    let mut seen = HashSet::new();
+    for target in find_links(&contents) {
+        if !seen.insert(target) {
+            continue;
+        }
+        if link_target_path(config, target).is_file() {
+            println!("{}", target);
+        } else {
+            println!("{}  (missing)", target);
+        }
+    }
+    Ok(())
+}
+
+/// Map each link target to the names of the notes linking to it, reading
+/// every note once
+fn backlink_index(config: &Config) -> io::Result<HashMap<String, BTreeSet<String>>> {
+    let mut index: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for path in note_paths(config)? {
+        if let Some(contents) = read_walked_note(config, &path)? {
+            let name = note_name(config, &path);
+            for target in find_links(&contents) {
+                index
+                    .entry(target.to_string())
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// List the notes that link to the note for `date`
+fn show_backlinks(config: &Config, date: Date) -> io::Result<()> {
+    let name = note_name(config, &get_note_path(config, date));
+    let mut index = backlink_index(config)?;
+    for source in index.remove(&name).unwrap_or_default() {
+        println!("{}", source);
+    }
+    Ok(())
+}
+
+/// Matches inline tags such as `#rust`, `#in-progress` or `#work/project-x`.
+/// Tags may contain `-` and `/` but must start and end with a word character,
+/// so trailing punctuation like `#tag.` is not captured.
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"#\w(?:[\w/-]*\w)?").unwrap());
+
+/// Find every inline tag in the given text, including the leading `#`
+fn find_tags(contents: &str) -> impl Iterator<Item = &str> {
+    TAG_RE.find_iter(contents).map(|m| m.as_str())
+}
+
+/// All tags used by a note: those declared in its frontmatter followed by
+/// its inline tags, each with a leading `#`
+fn note_tags(contents: &str) -> Vec<String> {
+    let declared = parse_frontmatter(contents)
+        .map(|frontmatter| frontmatter.tags)
+        .unwrap_or_default();
+    declared
+        .into_iter()
+        .map(|tag| format!("#{}", tag))
+        .chain(find_tags(contents).map(str::to_string))
+        .collect()
+}
+
+/// Count how often each tag occurs across all notes
+fn count_tags(config: &Config) -> io::Result<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+
+    for path in note_paths(config)? {
+        if let Some(contents) = read_walked_note(config, &path)? {
+            for tag in note_tags(&contents) {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Collect the notes using the given tag, oldest first. The tag may be given
+/// with or without its leading `#`.
+fn notes_with_tag(config: &Config, name: &str) -> io::Result<Vec<PathBuf>> {
+    let tag = format!("#{}", name.trim_start_matches('#'));
+    let mut paths = Vec::new();
+    for path in note_paths(config)? {
+        if read_walked_note(config, &path)?
+            .is_some_and(|contents| note_tags(&contents).contains(&tag))
+        {
+            paths.push(path);
+        }
+    }
+    sort_notes(config, &mut paths, true);
+    Ok(paths)
+}
+
+/// Replace whole-tag occurrences of `old` with `new` in the given text,
+/// returning the new text and the number of replacements. Both tags include
+/// their leading `#`.
+fn replace_tag(contents: &str, old: &str, new: &str) -> (String, usize) {
+    let mut replaced = 0;
+    let updated = TAG_RE.replace_all(contents, |caps: &regex::Captures| {
+        if &caps[0] == old {
+            replaced += 1;
+            new.to_string()
+        } else {
+            caps[0].to_string()
+        }
+    });
+    (updated.into_owned(), replaced)
+}
+
+/// Replace `old` with `new` among the tags declared in a note's frontmatter,
+/// as an inline or block list, keeping the list as written. Returns the new
+/// text and the number of replacements; both tags are without their `#`.
+fn replace_frontmatter_tag(contents: &str, old: &str, new: &str) -> (String, usize) {
+    if parse_frontmatter(contents).is_none() {
+        return (contents.to_string(), 0);
+    }
+    let is_old = |item: &str| {
+        item.trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .trim_start_matches('#')
+            == old
+    };
+    let mut replaced = 0;
+    let mut rename = |item: &str| {
+        if is_old(item) {
+            replaced += 1;
+            item.replacen(old, new, 1)
+        } else {
+            item.to_string()
+        }
+    };
+
+    let mut updated = String::with_capacity(contents.len());
+    let (mut in_tags, mut closed) = (false, false);
+    for (i, line) in contents.split_inclusive('\n').enumerate() {
+        let (text, ending) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        if closed || i == 0 {
+            updated.push_str(line);
+            continue;
+        }
+        if text.trim_end() == "---" {
+            closed = true;
+            updated.push_str(line);
+            continue;
+        }
+        if let Some(item) = text.trim_start().strip_prefix("- ") {
+            if in_tags {
+                let indent = &text[..text.len() - text.trim_start().len()];
+                updated.push_str(&format!("{}- {}{}", indent, rename(item), ending));
+            } else {
+                updated.push_str(line);
+            }
+            continue;
+        }
+        match text.split_once(':') {
+            Some((key, value)) => {
+                in_tags = key.trim() == "tags";
+                if in_tags {
+                    let items: Vec<String> = value.split(',').map(&mut rename).collect();
+                    updated.push_str(&format!("{}:{}{}", key, items.join(","), ending));
+                } else {
+                    updated.push_str(line);
+                }
+            }
+            None => updated.push_str(line),
+        }
+    }
+    (updated, replaced)
+}
+
+/// The `(line number, before, after)` of each line changed between two
+/// versions of a note with the same number of lines
+fn changed_lines<'a>(before: &'a str, after: &'a str) -> Vec<(usize, &'a str, &'a str)> {
+    before
+        .lines()
+        .zip(after.lines())
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(i, (old, new))| (i + 1, old, new))
+        .collect()
+}
+
+/// Rename a tag across all notes, rewriting only the files that change. With
+/// `preview` the changed lines are printed diff style and nothing is written.
+fn rename_tag(
+    config: &Config,
+    old: &str,
+    new: &str,
+    preview: bool,
+    dry_run: bool,
+) -> io::Result<()> {
+    let old = format!("#{}", old.trim_start_matches('#'));
+    let new = format!("#{}", new.trim_start_matches('#'));
+    if find_tags(&new).next() != Some(new.as_str()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a valid tag", new),
+        ));
+    }
+
+    // work out every replacement before writing, so a note that can't be
+    // read doesn't leave the rename half done
+    let mut changes = Vec::new();
+    for path in note_paths(config)? {
+        let Some(contents) = read_walked_note(config, &path)? else {
+            continue;
+        };
+        // `note_tags` also reads tags declared in frontmatter, so rename those too
+        let (declared, in_frontmatter) = replace_frontmatter_tag(&contents, &old[1..], &new[1..]);
+        let (updated, inline) = replace_tag(&declared, &old, &new);
+        let replaced = in_frontmatter + inline;
+        if replaced > 0 {
+            changes.push((path, contents, updated, replaced));
+        }
+    }
+
+    let color = config.color.enabled(io::stdout().is_terminal());
+    let (mut files, mut occurrences) = (0, 0);
+    for (path, contents, updated, replaced) in changes {
+        if preview {
+            println!("{}", paint(&path.display().to_string(), BOLD, color));
+            for (n, before, after) in changed_lines(&contents, &updated) {
+                println!("{}", paint(&format!("-{}: {}", n, before), RED, color));
+                println!("{}", paint(&format!("+{}: {}", n, after), GREEN, color));
+            }
+        } else if dry_run {
+            println!("Would update {} ({} occurrences)", path.display(), replaced);
+        } else {
+            fs::write(&path, updated)?;
+        }
+        files += 1;
+        occurrences += replaced;
+    }
+    println!(
+        "{} {} occurrences of {} to {} in {} files",
+        if preview || dry_run {
+            "Would rename"
+        } else {
+            "Renamed"
+        },
+        occurrences,
+        old,
+        new,
+        files
+    );
+    Ok(())
+}
+
+/// Notes dated on the same month and day as `today` in earlier years, newest first
+fn on_this_day(config: &Config, today: Date) -> io::Result<Vec<(Date, PathBuf)>> {
+    let mut notes: Vec<(Date, PathBuf)> = listed_note_paths(config)?
+        .into_iter()
+        .filter_map(|path| Some((note_date(config, &path)?, path)))
+        .filter(|(date, _)| {
+            date.year() < today.year() && (date.month(), date.day()) == (today.month(), today.day())
+        })
+        .collect();
+    notes.sort_by_key(|(date, _)| Reverse(*date));
+    Ok(notes)
+}
+
+/// Print the notes from this day in earlier years, as `date  heading` lines
+/// or in full with `show`
+fn show_on_this_day(config: &Config, today: Date, show: bool) -> io::Result<()> {
+    let notes = on_this_day(config, today)?;
+    if notes.is_empty() {
+        println!(
+            "No notes from {} in earlier years",
+            today.strftime("%B %-d")
+        );
+        return Ok(());
+    }
+    let color = config.color.enabled(io::stdout().is_terminal());
+    let mut shown = 0;
+    for (date, path) in &notes {
+        let Some(contents) = read_walked_note(config, path)? else {
+            continue;
+        };
+        if show {
+            if shown > 0 {
+                println!();
+            }
+            println!("{}", paint(&date.to_string(), BOLD, color));
+            print!("{}", contents);
+        } else {
+            let title = heading_title(path, &contents).unwrap_or_default();
+            println!("{}  {}", date, title);
+        }
+        shown += 1;
+    }
+    Ok(())
+}
+
+/// Pick a random note, uniformly or, with `old`, weighted by how many days
+/// before `today` it is dated (undated notes count as one day old)
+fn pick_random_note(
+    config: &Config,
+    paths: &[PathBuf],
+    old: bool,
+    today: Date,
+    rng: &mut fastrand::Rng,
+) -> Option<PathBuf> {
+    if paths.is_empty() {
+        return None;
+    }
+    if !old {
+        return Some(paths[rng.usize(..paths.len())].clone());
+    }
+    let weights: Vec<u64> = paths
+        .iter()
+        .map(|path| {
+            let age = note_date(config, path)
+                .and_then(|date| (today - date).get_days().try_into().ok())
+                .unwrap_or(0u64);
+            age + 1
+        })
+        .collect();
+    let mut pick = rng.u64(..weights.iter().sum::<u64>());
+    for (path, weight) in paths.iter().zip(weights) {
+        if pick < weight {
+            return Some(path.clone());
+        }
+        pick -= weight;
+    }
+    None
+}
+
+/// Print a random note, or open it in the editor with `edit`
+fn random_note(
+    config: &Config,
+    edit: bool,
+    old: bool,
+    today: Date,
+    dry_run: bool,
+) -> io::Result<()> {
+    let mut paths = listed_note_paths(config)?;
+    sort_notes(config, &mut paths, true);
+    let Some(path) = pick_random_note(config, &paths, old, today, &mut fastrand::Rng::new()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "there are no notes yet",
+        ));
+    };
+    if !edit {
+        let color = config.color.enabled(io::stdout().is_terminal());
+        println!("{}", paint(&note_name(config, &path), BOLD, color));
+        if let Some(contents) = read_walked_note(config, &path)? {
+            print!("{}", contents);
+        }
+    } else if dry_run {
+        let editor = resolve_editor(config);
+        println!("Would open {} in {}", path.display(), editor.join(" "));
+    } else {
+        open_editor(&path, &resolve_editor(config))?;
+        run_after_edit(config, &path)?;
+        autocommit(config, &format!("nn: update {}", note_name(config, &path)))?;
+    }
+    Ok(())
+}
+
+/// Print the notes that use the given tag
+fn list_tagged_notes(config: &Config, name: &str) -> io::Result<()> {
+    for path in notes_with_tag(config, name)? {
+        println!("{}", note_name(config, &path));
+    }
+    Ok(())
+}
+
+/// Order tag counts by frequency, most used first, breaking ties alphabetically
+fn sort_tag_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// Read a note up to its first heading and return the heading text
+fn note_title(path: &Path) -> io::Result<Option<String>> {
+    let reader = io::BufReader::new(fs::File::open(path)?);
+    let marker = heading_marker(path);
+    for line in reader.lines() {
+        if let Some(title) = line?.strip_prefix(marker) {
+            return Ok(Some(title.trim().to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// The text of the first heading in the already read `contents` of the note at `path`
+fn heading_title(path: &Path, contents: &str) -> Option<String> {
+    let marker = heading_marker(path);
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(marker))
+        .map(|title| title.trim().to_string())
+}
+
+/// Build the contents of `index.md`: every dated note, oldest first, under
+/// year and month headings, linked by its relative path with its title
+fn build_index(config: &Config) -> io::Result<String> {
+    let mut paths = listed_note_paths(config)?;
+    sort_notes(config, &mut paths, true);
+
+    let mut index = String::from("# Index\n");
+    let (mut year, mut month) = (None, None);
+    for path in &paths {
+        let Some(date) = note_date(config, path) else {
+            continue;
+        };
+        if year != Some(date.year()) {
+            year = Some(date.year());
+            month = None;
+            index.push_str(&format!("\n## {}\n", date.year()));
+        }
+        if month != Some(date.month()) {
+            month = Some(date.month());
+            index.push_str(&format!("\n### {}\n\n", date.strftime("%B")));
+        }
+        let name = note_name(config, path);
+        let title = note_title(path)?.unwrap_or_else(|| name.clone());
+        let relative = path.strip_prefix(&config.notes_dir).unwrap_or(path);
+        let link = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        index.push_str(&format!("- {}: [{}]({})\n", date, title, link));
+    }
+    Ok(index)
+}
+
+/// Regenerate `index.md`, leaving it untouched if nothing has changed
+fn write_index(config: &Config, dry_run: bool) -> io::Result<()> {
+    let path = index_path(config);
+    let index = build_index(config)?;
+    if fs::read_to_string(&path).is_ok_and(|old| old == index) {
+        println!("{} is up to date", path.display());
+    } else if dry_run {
+        println!("Would write {}", path.display());
+    } else {
+        fs::create_dir_all(&config.notes_dir)?;
+        fs::write(&path, index)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Whether every character of `query` appears in `text` in order, ignoring case
+fn fuzzy_matches(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| text.any(|t| t == q))
+}
+
+/// Print the notes whose name or title heading matches the query, ignoring case
+fn find_notes(config: &Config, query: &str, fuzzy: bool) -> io::Result<()> {
+    let lowered = query.to_lowercase();
+    let matches = |text: &str| {
+        if fuzzy {
+            fuzzy_matches(query, text)
+        } else {
+            text.to_lowercase().contains(&lowered)
+        }
+    };
+
+    let mut paths = note_paths(config)?;
+    sort_notes(config, &mut paths, false);
+    for path in paths {
+        let name = note_name(config, &path);
+        let title = note_title(&path).ok().flatten();
+        match title {
+            Some(title) if matches(&name) || matches(&title) => println!("{}  {}", name, title),
+            None if matches(&name) => println!("{}", name),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Print all notes whose name and title fuzzy-match the query, best match
+/// first, with their scores
+#[cfg(feature = "fuzzy")]
+fn fuzzy_find_notes(config: &Config, query: &str) -> io::Result<()> {
+    use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+    let matcher = SkimMatcherV2::default();
+    let mut ranked: Vec<(i64, String)> = note_paths(config)?
+        .iter()
+        .filter_map(|path| {
+            let name = note_name(config, path);
+            let text = match note_title(path).ok().flatten() {
+                Some(title) => format!("{}  {}", name, title),
+                None => name,
+            };
+            matcher.fuzzy_match(&text, query).map(|score| (score, text))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    for (score, text) in ranked {
+        println!("{:>4}  {}", score, text);
+    }
+    Ok(())
+}
+
+/// Write every matching line across all notes to `out` as `path:lineno:line`,
+/// a format editors like vim can load as a quickfix list, highlighted if
+/// `color` is set. Returns the number of matching lines.
+fn grep_notes(
+    config: &Config,
+    matcher: &Matcher,
+    color: bool,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut paths = note_paths(config)?;
+    sort_notes(config, &mut paths, true);
+    let mut total = 0;
+    for path in paths {
+        if let Some(contents) = read_walked_note(config, &path)? {
+            for (i, line) in contents.lines().enumerate() {
+                if matcher.is_match(line) {
+                    total += 1;
+                    writeln!(
+                        out,
+                        "{}{}{}{}{}",
+                        paint(&path.display().to_string(), MAGENTA, color),
+                        paint(":", CYAN, color),
+                        paint(&(i + 1).to_string(), GREEN, color),
+                        paint(":", CYAN, color),
+                        matcher.highlight(line, color)
+                    )?;
+                }
+            }
+        }
+    }
+    out.flush()?;
+    Ok(total)
+}
+
+/// Escape text for inclusion in HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The anchor on the exported tags page for a tag, e.g. `#rust` -> `tag-rust`
+fn tag_anchor(tag: &str) -> String {
+    format!("tag-{}", tag.trim_start_matches('#'))
+}
+
+/// The exported HTML filename for a note, flattening any subdirectories
+fn html_file_name(config: &Config, path: &Path) -> String {
+    format!("{}.html", note_name(config, path).replace('/', "-"))
+}
+
+/// Wrap an HTML body in a minimal standalone page
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body>\n<nav><a href=\"index.html\">Index</a> | <a href=\"tags.html\">Tags</a></nav>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+/// Render a note's markdown to HTML, turning inline tags outside of code and
+/// frontmatter into links to the tags page
+fn render_note_html(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut literal = false;
+    let mut events = Vec::new();
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => literal = true,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)) => literal = false,
+            Event::Text(ref text) if !literal => {
+                let mut last = 0;
+                for tag in TAG_RE.find_iter(text) {
+                    events.push(Event::Text(text[last..tag.start()].to_string().into()));
+                    events.push(Event::InlineHtml(
+                        format!(
+                            "<a class=\"tag\" href=\"tags.html#{}\">{}</a>",
+                            escape_html(&tag_anchor(tag.as_str())),
+                            escape_html(tag.as_str())
+                        )
+                        .into(),
+                    ));
+                    last = tag.end();
+                }
+                events.push(Event::Text(text[last..].to_string().into()));
+                continue;
+            }
+            _ => {}
+        }
+        events.push(event);
+    }
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    html
+}
+
+/// Export every note to an HTML page in `out`, along with an `index.html`
+/// listing the notes by date and a `tags.html` listing the notes for each tag,
+/// giving the number of notes exported
+fn export_html(config: &Config, out: &Path) -> io::Result<usize> {
+    fs::create_dir_all(out)?;
+    let mut paths = note_paths(config)?;
+    sort_notes(config, &mut paths, false);
+
+    let mut index = String::from("<h1>Notes</h1>\n<ul>\n");
+    let mut tagged: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    let mut exported = 0;
+    for path in &paths {
+        let Some(contents) = read_walked_note(config, path)? else {
+            continue;
+        };
+        let name = note_name(config, path);
+        let file_name = html_file_name(config, path);
+        fs::write(
+            out.join(&file_name),
+            html_page(&name, &render_note_html(&contents)),
+        )?;
+
+        // taken from the contents already read, which may have been repaired
+        let title = heading_title(path, &contents).unwrap_or_else(|| name.clone());
+        index.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> {}</li>\n",
+            escape_html(&file_name),
+            escape_html(&name),
+            escape_html(&title)
+        ));
+        let tags: BTreeSet<String> = note_tags(&contents).into_iter().collect();
+        for tag in tags {
+            tagged
+                .entry(tag)
+                .or_default()
+                .push((file_name.clone(), name.clone()));
+        }
+        exported += 1;
+    }
+    index.push_str("</ul>\n");
+    fs::write(out.join("index.html"), html_page("Notes", &index))?;
+
+    let mut tags_page = String::from("<h1>Tags</h1>\n");
+    for (tag, notes) in &tagged {
+        tags_page.push_str(&format!(
+            "<h2 id=\"{}\">{}</h2>\n<ul>\n",
+            escape_html(&tag_anchor(tag)),
+            escape_html(tag)
+        ));
+        for (file_name, name) in notes {
+            tags_page.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape_html(file_name),
+                escape_html(name)
+            ));
+        }
+        tags_page.push_str("</ul>\n");
+    }
+    fs::write(out.join("tags.html"), html_page("Tags", &tags_page))?;
+
+    Ok(exported)
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Render tag counts as lines of output: sorted by name, or with their counts
+/// by frequency if `count` is set
+fn format_tags(counts: HashMap<String, usize>, count: bool, format: TagFormat) -> Vec<String> {
+    let rows: Vec<(String, usize)> = if count {
+        sort_tag_counts(counts)
+    } else {
+        let mut rows: Vec<_> = counts.into_iter().collect();
+        rows.sort();
+        rows
+    };
+
+    match (format, count) {
+        (TagFormat::Plain, true) => {
+            let width = rows.iter().map(|(tag, _)| tag.len()).max().unwrap_or(0);
+            rows.iter()
+                .map(|(tag, n)| format!("{:<width$}  {}", tag, n, width = width))
+                .collect()
+        }
+        (TagFormat::Tsv, true) => rows
+            .iter()
+            .map(|(tag, n)| format!("{}\t{}", tag, n))
+            .collect(),
+        (TagFormat::Csv, true) => std::iter::once("tag,count".to_string())
+            .chain(
+                rows.iter()
+                    .map(|(tag, n)| format!("{},{}", csv_field(tag), n)),
+            )
+            .collect(),
+        (TagFormat::Plain | TagFormat::Tsv, false) => {
+            rows.into_iter().map(|(tag, _)| tag).collect()
+        }
+        (TagFormat::Csv, false) => std::iter::once("tag".to_string())
+            .chain(rows.iter().map(|(tag, _)| csv_field(tag)))
+            .collect(),
+    }
+}
+
+/// Extract and print all unique tags used in notes, optionally with how
+/// often each one is used
+fn extract_tags(config: &Config, count: bool, format: TagFormat) -> io::Result<()> {
+    let format = if config.porcelain {
+        TagFormat::Tsv
+    } else {
+        format
+    };
+    for line in format_tags(count_tags(config)?, count, format) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// A markdown task list item such as `- [ ] reply to Sam`
+#[derive(Debug, PartialEq)]
+struct Todo {
+    /// 1-based line number within the note
+    line: usize,
+    done: bool,
+    text: String,
+}
+
+/// Matches a task list item, capturing its checkbox state and text
+static TODO_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*[-*+] \[([ xX])\] (.*)$").unwrap());
+
+/// Find the task list items in a note, in order
+fn find_todos(contents: &str) -> Vec<Todo> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let caps = TODO_RE.captures(line)?;
+            Some(Todo {
+                line: i + 1,
+                done: &caps[1] != " ",
+                text: caps[2].trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Tick the open task on 1-based line `line`, changing `[ ]` to `[x]` and
+/// leaving the rest of the note byte-for-byte intact
+fn complete_todo(contents: &str, line: usize) -> Option<String> {
+    let mut out = String::with_capacity(contents.len());
+    let mut found = false;
+    for (i, text) in contents.split_inclusive('\n').enumerate() {
+        if i + 1 == line {
+            let caps = TODO_RE.captures(text.trim_end_matches(['\r', '\n']))?;
+            if &caps[1] != " " {
+                return None;
+            }
+            let checkbox = caps.get(1).unwrap().start();
+            out.push_str(&text[..checkbox]);
+            out.push('x');
+            out.push_str(&text[checkbox + 1..]);
+            found = true;
+        } else {
+            out.push_str(text);
+        }
+    }
+    found.then_some(out)
+}
+
+/// Mark the open task on `line` of the note for `date` as done
+fn mark_done(config: &Config, date: Date, line: usize, dry_run: bool) -> io::Result<()> {
+    let path = get_note_path(config, date);
+    if !path.exists() && encrypted_path(&path).exists() {
+        return Err(note_encrypted(date, "use `nn edit` instead"));
+    }
+    let contents = read_note(config, date)?;
+    let updated = complete_todo(&contents, line).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("line {} of {} is not an open task", line, date),
+        )
+    })?;
+    let todo = find_todos(&updated)
+        .into_iter()
+        .find(|todo| todo.line == line)
+        .map(|todo| todo.text)
+        .unwrap_or_default();
+    if dry_run {
+        println!("Would mark done: {}", todo);
+        return Ok(());
+    }
+    fs::write(path, updated)?;
+    println!("Done: {}", todo);
+    Ok(())
+}
+
+/// Print the tasks in every note as `note:line  [ ] text`, oldest note first,
+/// leaving out completed ones unless `all`
+fn list_todos(config: &Config, all: bool) -> io::Result<()> {
+    let mut paths = note_paths(config)?;
+    sort_notes(config, &mut paths, true);
+    for path in paths {
+        let Some(contents) = read_walked_note(config, &path)? else {
+            continue;
+        };
+        for todo in find_todos(&contents) {
+            if all || !todo.done {
+                let mark = if todo.done { 'x' } else { ' ' };
+                println!(
+                    "{}:{}  [{}] {}",
+                    note_name(config, &path),
+                    todo.line,
+                    mark,
+                    todo.text
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Count the words in a note, ignoring markdown heading lines
+fn count_words(contents: &str) -> usize {
+    contents
+        .lines()
+        .filter(|line| !is_heading(line))
+        .map(|line| line.split_whitespace().count())
+        .sum()
+}
+
+/// Whether a line is a markdown ATX heading such as `# Title` or `## Section`
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    (1..=6).contains(&hashes)
+        && line[hashes..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+/// The number of consecutive days with a note, counting back from `today`.
+/// `dates` must be sorted; the streak is 0 if there is no note for today.
+fn current_streak(dates: &[Date], today: Date) -> usize {
+    let mut streak = 0;
+    let mut day = today;
+    while dates.binary_search(&day).is_ok() {
+        streak += 1;
+        match day.yesterday() {
+            Ok(previous) => day = previous,
+            Err(_) => break,
+        }
+    }
+    streak
+}
+
+/// The length of the longest run of consecutive days in sorted `dates`
+fn longest_streak(dates: &[Date]) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+    for &date in dates {
+        let follows = previous
+            .and_then(|p| p.checked_add(1.day()).ok())
+            .is_some_and(|next| next == date);
+        run = if follows { run + 1 } else { 1 };
+        longest = longest.max(run);
+        previous = Some(date);
+    }
+    longest
+}
+
+/// The days in the `days`-day window ending on `today` that have no note
+fn missing_dates(dates: &[Date], today: Date, days: i64) -> Vec<Date> {
+    (0..days)
+        .rev()
+        .filter_map(|n| today.checked_sub(n.days()).ok())
+        .filter(|day| dates.binary_search(day).is_err())
+        .collect()
+}
+
+/// The distinct dates of the given notes, in ascending order
+fn sorted_note_dates(config: &Config, paths: &[PathBuf]) -> Vec<Date> {
+    let mut dates: Vec<Date> = paths
+        .iter()
+        .filter_map(|path| note_date(config, path))
+        .collect();
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/// Print the current and longest daily-note streaks and the days missed in
+/// the last 30 days
+fn show_streak(config: &Config) -> io::Result<()> {
+    let mut paths = note_paths(config)?;
+    paths.extend(encrypted_note_paths(config)?);
+    let dates = sorted_note_dates(config, &paths);
+    let today = config.now()?.date();
+
+    print_table(&[
+        (
+            "Current streak",
+            format!("{} days", current_streak(&dates, today)),
+        ),
+        ("Longest streak", format!("{} days", longest_streak(&dates))),
+    ]);
+    let missing = missing_dates(&dates, today, 30);
+    if missing.is_empty() {
+        println!("\nNo missing days in the last 30 days");
+    } else {
+        println!("\nMissing in the last 30 days:");
+        for date in missing {
+            println!("  {}", date);
+        }
+    }
+    Ok(())
+}
+
+/// Format a byte count with a binary unit, e.g. `1.5 KiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Print a table of `label  value` rows with the values aligned
+fn print_table(rows: &[(&str, String)]) {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    for (label, value) in rows {
+        println!("{:<width$}  {}", label, value, width = width);
+    }
+}
+
+/// Print a summary of the notes collection: counts, sizes, date range and streak
+fn show_stats(config: &Config) -> io::Result<()> {
+    let mut paths = listed_note_paths(config)?;
+    paths.extend(encrypted_note_paths(config)?);
+    let (mut words, mut bytes, mut read) = (0, 0, 0);
+    let mut tags = HashSet::new();
+    for path in &paths {
+        bytes += fs::metadata(path)?.len();
+        // encrypted notes count towards the totals but their words can't be read
+        if is_encrypted(path) {
+            continue;
+        }
+        if let Some(contents) = read_walked_note(config, path)? {
+            read += 1;
+            words += count_words(&contents);
+            tags.extend(note_tags(&contents));
+        }
+    }
+
+    let dates = sorted_note_dates(config, &paths);
+    let range = match (dates.first(), dates.last()) {
+        (Some(first), Some(last)) => format!("{} to {}", first, last),
+        _ => "-".to_string(),
+    };
+    let average = if read == 0 {
+        0.0
+    } else {
+        words as f64 / read as f64
+    };
+    let streak = current_streak(&dates, config.now()?.date());
+
+    print_table(&[
+        ("Notes", paths.len().to_string()),
+        ("Words", words.to_string()),
+        ("Size", format_size(bytes)),
+        ("Date range", range),
+        ("Average words", format!("{:.1}", average)),
+        ("Unique tags", tags.len().to_string()),
+        ("Current streak", format!("{} days", streak)),
+    ]);
+    Ok(())
+}
+
+/// Total up the notes, distinct tags or words in the collection. Counting
+/// notes only walks the notes directory without reading any of them.
+fn count_total(config: &Config, what: CountKind) -> io::Result<usize> {
+    let paths = listed_note_paths(config)?;
+    if let CountKind::Notes = what {
+        return Ok(paths.len() + encrypted_note_paths(config)?.len());
+    }
+    let (mut words, mut tags) = (0, HashSet::new());
+    for path in &paths {
+        if let Some(contents) = read_walked_note(config, path)? {
+            match what {
+                CountKind::Words => words += count_words(&contents),
+                _ => tags.extend(note_tags(&contents)),
+            }
+        }
+    }
+    Ok(match what {
+        CountKind::Words => words,
+        _ => tags.len(),
+    })
+}
+
+/// The `label  value` rows `nn info` shows for a note: where it is, its size
+/// and timestamps in `tz`, prose counts, tags and any frontmatter fields
+fn note_info(path: &Path, contents: &str, tz: &TimeZone) -> io::Result<Vec<(String, String)>> {
+    let metadata = fs::metadata(path)?;
+    let timestamp = |time: io::Result<std::time::SystemTime>| {
+        time.ok()
+            .and_then(|time| Timestamp::try_from(time).ok())
+            .map(|ts| {
+                ts.to_zoned(tz.clone())
+                    .strftime("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let counts = TextCounts::of(contents);
+    let tags: BTreeSet<String> = note_tags(contents).into_iter().collect();
+    let tags = if tags.is_empty() {
+        "-".to_string()
+    } else {
+        tags.into_iter().collect::<Vec<_>>().join(" ")
+    };
+
+    let mut rows = vec![
+        ("Path".to_string(), path.display().to_string()),
+        ("Size".to_string(), format_size(metadata.len())),
+        ("Created".to_string(), timestamp(metadata.created())),
+        ("Modified".to_string(), timestamp(metadata.modified())),
+        ("Words".to_string(), counts.words.to_string()),
+        ("Lines".to_string(), contents.lines().count().to_string()),
+        ("Tags".to_string(), tags),
+    ];
+    if let Some(frontmatter) = parse_frontmatter(contents) {
+        rows.extend(frontmatter.fields);
+    }
+    Ok(rows)
+}
+
+/// Print the metadata table for the note on `date`
+fn show_info(config: &Config, date: Date) -> io::Result<()> {
+    let contents = read_note(config, date)?;
+    let path = get_note_path(config, date);
+    let path = if path.exists() {
+        path
+    } else {
+        encrypted_path(&path)
+    };
+    let rows = note_info(&path, &contents, config.now()?.time_zone())?;
+    let rows: Vec<(&str, String)> = rows
+        .iter()
+        .map(|(label, value)| (label.as_str(), value.clone()))
+        .collect();
+    print_table(&rows);
+    Ok(())
+}
+
+/// Reduce markdown to its prose by dropping heading hashes, list and quote
+/// markers, emphasis characters and fence lines
+fn strip_markdown(markdown: &str) -> String {
+    static LIST_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*(?:>\s*)*(?:[-*+]|\d+[.)])\s+(?:\[[ xX]\]\s+)?").unwrap()
+    });
+    let mut out = String::new();
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            continue;
+        }
+        let line = if is_heading(line) {
+            line.trim_start_matches('#')
+        } else {
+            line
+        };
+        let line = LIST_RE.replace(line, "");
+        let line = line.trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+        let prose: String = line
+            .chars()
+            .filter(|c| !matches!(c, '*' | '_' | '`' | '~'))
+            .collect();
+        out.push_str(prose.trim());
+        out.push('\n');
+    }
+    out
+}
+
+/// Words, non-blank lines and non-whitespace characters of prose in a note
+#[derive(Debug, Default, PartialEq)]
+struct TextCounts {
+    words: usize,
+    lines: usize,
+    chars: usize,
+}
+
+impl TextCounts {
+    fn of(markdown: &str) -> Self {
+        let prose = strip_markdown(markdown);
+        TextCounts {
+            words: prose.split_whitespace().count(),
+            lines: prose.lines().filter(|line| !line.is_empty()).count(),
+            chars: prose.chars().filter(|c| !c.is_whitespace()).count(),
+        }
+    }
+
+    fn add(&mut self, other: &TextCounts) {
+        self.words += other.words;
+        self.lines += other.lines;
+        self.chars += other.chars;
+    }
+}
+
+/// Report prose counts for the note on `date`, or for every note plus a total
+fn word_count(config: &Config, date: Option<Date>) -> io::Result<()> {
+    if let Some(date) = date {
+        let counts = TextCounts::of(&read_note(config, date)?);
+        print_table(&[
+            ("Words", counts.words.to_string()),
+            ("Lines", counts.lines.to_string()),
+            ("Characters", counts.chars.to_string()),
+        ]);
+        return Ok(());
+    }
+
+    let mut paths = note_paths(config)?;
+    sort_notes(config, &mut paths, true);
+    let mut rows = vec![[
+        "Note".to_string(),
+        "Words".to_string(),
+        "Lines".to_string(),
+        "Characters".to_string(),
+    ]];
+    let mut total = TextCounts::default();
+    for path in &paths {
+        let Some(contents) = read_walked_note(config, path)? else {
+            continue;
+        };
+        let counts = TextCounts::of(&contents);
+        total.add(&counts);
+        rows.push([
+            note_name(config, path),
+            counts.words.to_string(),
+            counts.lines.to_string(),
+            counts.chars.to_string(),
+        ]);
+    }
+    rows.push([
+        "Total".to_string(),
+        total.words.to_string(),
+        total.lines.to_string(),
+        total.chars.to_string(),
+    ]);
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        println!(
+            "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        );
+    }
+    Ok(())
+}
+
+/// The lines of one month of the calendar: its name, the weekday initials
+/// and six weeks of days, each cell two columns wide. Days with a note are
+/// `█`, shaded by their word count when `color` is set, and other days `·`.
+fn month_grid(year: i16, month: i8, words: &HashMap<Date, usize>, color: bool) -> Vec<String> {
+    let first = Date::new(year, month, 1).expect("valid month");
+    let mut lines = vec![
+        format!("{:<14}", first.strftime("%B").to_string()),
+        "M T W T F S S ".to_string(),
+    ];
+    let offset = first.weekday().to_monday_zero_offset();
+    for week in 0..6 {
+        let mut line = String::new();
+        for weekday in 0..7 {
+            let day = week * 7 + weekday - offset + 1;
+            if day < 1 || day > first.days_in_month() {
+                line.push_str("  ");
+                continue;
+            }
+            let cell = match words.get(&Date::new(year, month, day).expect("valid day")) {
+                None => paint("·", DIM, color),
+                Some(&count) if count < 100 => paint("█", &format!("{};{}", DIM, GREEN), color),
+                Some(&count) if count < 500 => paint("█", GREEN, color),
+                Some(_) => paint("█", &format!("{};{}", BOLD, GREEN), color),
+            };
+            line.push_str(&cell);
+            line.push(' ');
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Print the calendar for `year`, three months to a row
+fn show_calendar(config: &Config, year: i16) -> io::Result<()> {
+    let mut words = HashMap::new();
+    for path in note_paths(config)? {
+        let Some(date) = note_date(config, &path).filter(|date| date.year() == year) else {
+            continue;
+        };
+        let count = read_walked_note(config, &path)?
+            .map(|contents| count_words(&contents))
+            .unwrap_or(0);
+        *words.entry(date).or_insert(0) += count;
+    }
+
+    let color = config.color.enabled(io::stdout().is_terminal());
+    println!("{}", year);
+    let months: Vec<Vec<String>> = (1..=12)
+        .map(|month| month_grid(year, month, &words, color))
+        .collect();
+    for row in months.chunks(3) {
+        println!();
+        for line in 0..row[0].len() {
+            let cells: Vec<&str> = row.iter().map(|month| month[line].as_str()).collect();
+            println!("{}", cells.join("  ").trim_end());
+        }
+    }
+    Ok(())
+}
+
+/// Whether a filesystem event in a note's directory is a change to the note
+#[cfg(feature = "watch")]
+fn is_note_change(event: &notify::Event, path: &Path) -> bool {
+    use notify::EventKind;
+
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == path.file_name())
+}
+
+/// Open the note for `date` in the editor and, each time the note is written,
+/// run the `after_edit` hook and re-export the notes to HTML in `export`, if
+/// given, until the editor exits. GUI editors need an `editor_args` such as
+/// `--wait` to stay open. Output from the hook and the export is held back
+/// so it can't garble a terminal editor, and reported once the editor exits.
+#[cfg(feature = "watch")]
+fn watch_note(config: &Config, date: Date, export: Option<&Path>, dry_run: bool) -> io::Result<()> {
+    use notify::{RecursiveMode, Watcher};
+    use std::{
+        sync::{atomic::AtomicBool, mpsc},
+        thread,
+        time::Duration,
+    };
+
+    let hook = config.after_edit.as_deref();
+    if hook.is_none() && export.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "nothing to run on save, set after_edit in the config or pass --command or --export",
+        ));
+    }
+    let path = get_note_path(config, date);
+    let editor = resolve_editor(config);
+    if dry_run {
+        let mut actions = Vec::new();
+        if let Some(hook) = hook {
+            actions.push(format!("run '{}'", hook));
+        }
+        if let Some(out) = export {
+            actions.push(format!("export to {}", out.display()));
+        }
+        println!(
+            "Would open {} in {} and {} on each save",
+            path.display(),
+            editor.join(" "),
+            actions.join(" and ")
+        );
+        return Ok(());
+    }
+    create_note_if_missing(config, &path, date)?;
+
+    // editors often save by replacing the file, so watch its directory
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+    let dir = path.parent().unwrap_or(&config.notes_dir);
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+
+    let done = AtomicBool::new(false);
+    let (saves, problems) = thread::scope(|scope| {
+        let worker = scope.spawn(|| {
+            // receivers can't be shared, so move this one in
+            let rx = rx;
+            let (mut saves, mut problems) = (0, Vec::new());
+            loop {
+                // only stop once the editor's last save has been handled
+                let event = match rx.recv_timeout(Duration::from_millis(100)) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout)
+                        if !done.load(atomic::Ordering::Relaxed) =>
+                    {
+                        continue
+                    }
+                    Err(_) => break,
+                };
+                match event {
+                    Ok(event) if is_note_change(&event, &path) => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        problems.push(format!("watching {} failed: {}", dir.display(), e));
+                        break;
+                    }
+                }
+                // one save can raise several events, so wait for them to settle
+                while rx.recv_timeout(Duration::from_millis(200)).is_ok() {}
+                saves += 1;
+                if let Some(hook) = hook {
+                    match hook_command(hook, &path).output() {
+                        Ok(output) if output.status.success() => {}
+                        Ok(output) => problems.push(format!(
+                            "after_edit hook '{}' failed ({}): {}",
+                            hook,
+                            output.status,
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        )),
+                        Err(e) => problems
+                            .push(format!("could not run after_edit hook '{}': {}", hook, e)),
+                    }
+                }
+                if let Some(out) = export {
+                    if let Err(e) = export_html(config, out) {
+                        problems.push(format!("export to {} failed: {}", out.display(), e));
+                    }
+                }
+            }
+            (saves, problems)
+        });
+        let edited = open_editor(&path, &editor);
+        done.store(true, atomic::Ordering::Relaxed);
+        edited.map(|()| worker.join().expect("watch thread panicked"))
+    })?;
+    drop(watcher);
+
+    for problem in &problems {
+        eprintln!("Warning: {}", problem);
+    }
+    println!("Handled {} saves of {}", saves, path.display());
+    Ok(())
+}
+
+/// A note in the TUI, loaded up front so filtering doesn't touch the disk
+#[cfg(feature = "tui")]
+struct TuiNote {
+    name: String,
+    path: PathBuf,
+    contents: String,
+    tags: Vec<String>,
+}
+
+/// What keys typed in the TUI currently do
+#[cfg(feature = "tui")]
+#[derive(Clone, Copy, PartialEq)]
+enum TuiMode {
+    Browse,
+    Search,
+    Tag,
+    ConfirmDelete,
+}
+
+/// State of the TUI: every note, the ones passing the filters and the selection
+#[cfg(feature = "tui")]
+struct TuiApp {
+    notes: Vec<TuiNote>,
+    visible: Vec<usize>,
+    list: ratatui::widgets::ListState,
+    mode: TuiMode,
+    query: String,
+    tag_input: String,
+    tag: Option<String>,
+    status: String,
+}
+
+#[cfg(feature = "tui")]
+impl TuiApp {
+    /// Load every note, newest first
+    fn load(config: &Config) -> io::Result<Self> {
+        let mut app = TuiApp {
+            notes: Vec::new(),
+            visible: Vec::new(),
+            list: ratatui::widgets::ListState::default(),
+            mode: TuiMode::Browse,
+            query: String::new(),
+            tag_input: String::new(),
+            tag: None,
+            status: String::new(),
+        };
+        app.reload(config)?;
+        Ok(app)
+    }
+
+    /// Re-read the notes from disk, keeping the filters
+    fn reload(&mut self, config: &Config) -> io::Result<()> {
+        let mut paths = listed_note_paths(config)?;
+        sort_notes(config, &mut paths, false);
+        self.notes.clear();
+        for path in paths {
+            let Some(contents) = read_walked_note(config, &path)? else {
+                continue;
+            };
+            self.notes.push(TuiNote {
+                name: note_name(config, &path),
+                tags: note_tags(&contents),
+                path,
+                contents,
+            });
+        }
+        self.refilter();
+        Ok(())
+    }
+
+    /// Recompute the visible notes from the search query and tag filter
+    fn refilter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.visible = (0..self.notes.len())
+            .filter(|&i| {
+                let note = &self.notes[i];
+                let matches_query = query.is_empty()
+                    || note.name.to_lowercase().contains(&query)
+                    || note.contents.to_lowercase().contains(&query);
+                let matches_tag = self.tag.as_ref().is_none_or(|tag| note.tags.contains(tag));
+                matches_query && matches_tag
+            })
+            .collect();
+        let selected = match self.visible.len() {
+            0 => None,
+            len => Some(self.list.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.list.select(selected);
+    }
+
+    fn selected(&self) -> Option<&TuiNote> {
+        self.list
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&i| &self.notes[i])
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if let Some(selected) = self.list.selected() {
+            let last = self.visible.len().saturating_sub(1);
+            self.list
+                .select(Some(selected.saturating_add_signed(delta).min(last)));
+        }
+    }
+}
+
+/// Draw the note list, the preview of the selected note and the status line
+#[cfg(feature = "tui")]
+fn draw_tui(frame: &mut ratatui::Frame, app: &mut TuiApp) {
+    use ratatui::{
+        layout::{Constraint, Layout},
+        style::{Modifier, Style},
+        widgets::{Block, List, Paragraph, Wrap},
+    };
+
+    let area = frame.area();
+    if area.width < 40 || area.height < 8 {
+        frame.render_widget(
+            Paragraph::new("Terminal too small for nn tui, make it at least 40x8 (q quits)")
+                .wrap(Wrap { trim: true }),
+            area,
+        );
+        return;
+    }
+
+    let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(area);
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)]).areas(main);
+
+    let mut title = format!("Notes ({})", app.visible.len());
+    if let Some(tag) = &app.tag {
+        title.push_str(&format!(" {}", tag));
+    }
+    if !app.query.is_empty() {
+        title.push_str(&format!(" /{}", app.query));
+    }
+    let names: Vec<&str> = app
+        .visible
+        .iter()
+        .map(|&i| app.notes[i].name.as_str())
+        .collect();
+    let list = List::new(names)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, left, &mut app.list);
+
+    let (name, preview) = match app.selected() {
+        Some(note) => (
+            note.name.clone(),
+            render_markdown_ansi(&note.contents, false),
+        ),
+        None => (String::new(), "No matching notes".to_string()),
+    };
+    frame.render_widget(
+        Paragraph::new(preview)
+            .block(Block::bordered().title(name))
+            .wrap(Wrap { trim: false }),
+        right,
+    );
+
+    let line = match app.mode {
+        TuiMode::Search => format!("/{}", app.query),
+        TuiMode::Tag => format!("tag: {}", app.tag_input),
+        TuiMode::ConfirmDelete => format!(
+            "Delete {}? [y/N]",
+            app.selected().map_or("", |note| note.name.as_str())
+        ),
+        TuiMode::Browse if !app.status.is_empty() => app.status.clone(),
+        TuiMode::Browse => {
+            "j/k move  enter edit  / search  t tag  esc clear  d delete  q quit".to_string()
+        }
+    };
+    frame.render_widget(Paragraph::new(line), status);
+}
+
+/// Run the full-screen note browser until the user quits
+#[cfg(feature = "tui")]
+fn run_tui(config: &Config, dry_run: bool) -> io::Result<()> {
+    let mut app = TuiApp::load(config)?;
+    let mut terminal = ratatui::init();
+    let result = tui_loop(config, &mut terminal, &mut app, dry_run);
+    ratatui::restore();
+    result
+}
+
+#[cfg(feature = "tui")]
+fn tui_loop(
+    config: &Config,
+    terminal: &mut ratatui::DefaultTerminal,
+    app: &mut TuiApp,
+    dry_run: bool,
+) -> io::Result<()> {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+
+    loop {
+        terminal.draw(|frame| draw_tui(frame, app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match (app.mode, key.code) {
+            (TuiMode::Browse, KeyCode::Char('q')) => return Ok(()),
+            (TuiMode::Browse, KeyCode::Esc) => {
+                app.query.clear();
+                app.tag = None;
+                app.status.clear();
+                app.refilter();
+            }
+            (TuiMode::Browse, KeyCode::Down | KeyCode::Char('j')) => app.move_selection(1),
+            (TuiMode::Browse, KeyCode::Up | KeyCode::Char('k')) => app.move_selection(-1),
+            (TuiMode::Browse, KeyCode::Char('/')) => app.mode = TuiMode::Search,
+            (TuiMode::Browse, KeyCode::Char('t')) => {
+                app.tag_input.clear();
+                app.mode = TuiMode::Tag;
+            }
+            (TuiMode::Browse, KeyCode::Char('d')) if app.selected().is_some() => {
+                app.mode = TuiMode::ConfirmDelete
+            }
+            (TuiMode::Browse, KeyCode::Enter | KeyCode::Char('e')) => {
+                let Some(note) = app.selected() else {
+                    continue;
+                };
+                let (path, name) = (note.path.clone(), note.name.clone());
+                ratatui::restore();
+                let edited = open_editor(&path, &resolve_editor(config));
+                *terminal = ratatui::init();
+                edited?;
+                run_after_edit(config, &path)?;
+                autocommit(config, &format!("nn: update {}", name))?;
+                app.reload(config)?;
+                app.status = format!("Edited {}", name);
+            }
+            (TuiMode::Search, KeyCode::Char(c)) => {
+                app.query.push(c);
+                app.refilter();
+            }
+            (TuiMode::Search, KeyCode::Backspace) => {
+                app.query.pop();
+                app.refilter();
+            }
+            (TuiMode::Search, KeyCode::Esc) => {
+                app.query.clear();
+                app.refilter();
+                app.mode = TuiMode::Browse;
+            }
+            (TuiMode::Search, KeyCode::Enter) => app.mode = TuiMode::Browse,
+            (TuiMode::Tag, KeyCode::Char(c)) => app.tag_input.push(c),
+            (TuiMode::Tag, KeyCode::Backspace) => {
+                app.tag_input.pop();
+            }
+            (TuiMode::Tag, KeyCode::Esc) => app.mode = TuiMode::Browse,
+            (TuiMode::Tag, KeyCode::Enter) => {
+                let tag = app.tag_input.trim().trim_start_matches('#');
+                app.tag = (!tag.is_empty()).then(|| format!("#{}", tag));
+                app.refilter();
+                app.mode = TuiMode::Browse;
+            }
+            (TuiMode::ConfirmDelete, KeyCode::Char('y')) => {
+                if let Some(note) = app.selected() {
+                    let (path, name) = (note.path.clone(), note.name.clone());
+                    if dry_run {
+                        app.status = format!("Would delete {}", path.display());
+                    } else {
+                        fs::remove_file(&path)?;
+                        autocommit(config, &format!("nn: delete {}", name))?;
+                        app.reload(config)?;
+                        app.status = format!("Deleted {}", name);
+                    }
+                }
+                app.mode = TuiMode::Browse;
+            }
+            (TuiMode::ConfirmDelete, _) => app.mode = TuiMode::Browse,
+            _ => {}
+        }
+    }
+}
+
+/// Point the config at a different notes directory for this invocation, the
+/// `--notes-dir` flag taking precedence over the `NN_NOTES_DIR` variable.
+/// The directory is created if needed, as on first run.
+fn override_notes_dir(
+    config: &mut Config,
+    flag: Option<PathBuf>,
+    env_value: Option<PathBuf>,
+) -> io::Result<()> {
+    if let Some(notes_dir) = flag.or(env_value) {
+        fs::create_dir_all(&notes_dir)?;
+        config.notes_dir = notes_dir;
+    }
+    Ok(())
+}
+
+/// Failures that scripts may want to tell apart, each with its own exit code
+#[derive(Debug)]
+enum NnError {
+    /// There is no note for the requested date
+    NotFound(String),
+    /// The config file could not be found, read or understood
+    Config(String),
+    /// A date or date range argument could not be understood
+    InvalidDate(String),
+    /// Nothing matched a `search -q` or `search -l`, which is only told by the exit code
+    NoMatch,
+}
+
+impl fmt::Display for NnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NnError::NotFound(message)
+            | NnError::Config(message)
+            | NnError::InvalidDate(message) => f.write_str(message),
+            NnError::NoMatch => f.write_str("no notes matched"),
+        }
+    }
+}
+
+impl Error for NnError {}
+
+impl NnError {
+    /// The process exit code for this failure
+    fn exit_code(&self) -> i32 {
+        match self {
+            NnError::NotFound(_) => 2,
+            NnError::Config(_) => 3,
+            NnError::InvalidDate(_) => 4,
+            NnError::NoMatch => 1,
+        }
+    }
+
+    /// Wrap any error raised while loading the config as a config error
+    fn config(error: impl fmt::Display) -> Self {
+        NnError::Config(error.to_string())
+    }
+}
+
+/// The error for a date with no note
+fn note_not_found(date: Date) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        NnError::NotFound(format!("no note found for {}", date)),
+    )
+}
+
+/// The exit code for an error returned by `run`: those of `NnError`, even
+/// when wrapped in an `io::Error`, and 1 for everything else
+fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    let nn_error = error.downcast_ref::<NnError>().or_else(|| {
+        error
+            .downcast_ref::<io::Error>()
+            .and_then(|e| e.get_ref())
+            .and_then(|inner| inner.downcast_ref::<NnError>())
+    });
+    nn_error.map_or(1, NnError::exit_code)
+}
+
+/// Run the parsed command, returning any error to `main` for reporting
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    // completions don't need a config, so don't create one just for them
+    if let Some(Commands::Completions { shell }) = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "nn", &mut io::stdout());
+        return Ok(());
+    }
+    let config_file = config_file(cli.config).map_err(NnError::config)?;
+    // nor does the config subcommand, which must work when the config is broken
+    if let Some(Commands::Config { action }) = &cli.command {
+        match action {
+            ConfigAction::Path => println!("{}", config_file.display()),
+            ConfigAction::Edit => edit_config(&config_file, cli.editor.clone())?,
+        }
+        return Ok(());
+    }
+
+    let mut config = load_or_init_config(&config_file).map_err(NnError::config)?;
+    config.check_date_format().map_err(NnError::config)?;
+    // state such as pins lives beside the config file
+    let pins_path = Pins::path(config_file.parent().unwrap_or(Path::new("")));
+    config
+        .apply_profile(cli.profile.as_deref())
+        .map_err(NnError::config)?;
+    override_notes_dir(
+        &mut config,
+        cli.notes_dir,
+        env::var_os("NN_NOTES_DIR").map(PathBuf::from),
+    )?;
+    if cli.no_commit || cli.dry_run {
+        config.git_autocommit = false;
+    }
+    config.editor_override = cli.editor;
+    config.color = cli.color;
+    config.strict = cli.strict;
+    config.porcelain = cli.porcelain;
+    config.ignore.extend(cli.ignore);
+    if cli.porcelain {
+        config.color = ColorChoice::Never;
+    }
+    let now = config.now().map_err(NnError::config)?;
+    let resolve = |date: &str| resolve_date(date, now.date()).map_err(NnError::InvalidDate);
+    verbose!(
+        1,
+        "using timezone {}",
+        now.time_zone().iana_name().unwrap_or("(system)")
+    );
+    verbose!(1, "using notes directory {}", config.notes_dir.display());
+
+    match cli.command {
+        Some(Commands::Completions { .. } | Commands::Config { .. }) => {
+            unreachable!("handled before loading the config")
+        }
+        Some(Commands::Delete {
+            date,
+            yes,
+            archive,
+            with_attachments,
+            range,
+        }) => {
+            let range = range.resolve(now.date()).map_err(NnError::InvalidDate)?;
+            match date {
+                Some(date) if !is_glob(&date) => {
+                    if !range.is_unbounded() {
+                        return Err("a single date cannot be combined with --from/--to".into());
+                    }
+                    let date = resolve(&date)?;
+                    delete_note(&config, date, yes, archive, with_attachments, cli.dry_run)?;
+                    autocommit(&config, &format!("nn: delete {}", date))?
+                }
+                pattern => {
+                    let glob = pattern.as_deref().map(glob_regex).transpose()?;
+                    let paths = matching_notes(&config, glob.as_ref(), range)?;
+                    delete_notes(&config, &paths, yes, archive, with_attachments, cli.dry_run)?;
+                    autocommit(&config, &format!("nn: delete {} notes", paths.len()))?
+                }
+            }
+        }
+        Some(Commands::Edit { date, encrypt }) => {
+            let date = resolve(&date)?;
+            if encrypt {
+                edit_encrypted_note(&config, date, cli.dry_run)?;
+            } else {
+                edit_note(&config, date, cli.dry_run)?;
+            }
+            autocommit(&config, &format!("nn: update {}", date))?
+        }
+        Some(Commands::Done { date, line }) => {
+            let date = resolve(&date)?;
+            mark_done(&config, date, line, cli.dry_run)?;
+            autocommit(&config, &format!("nn: complete task in {}", date))?
+        }
+        Some(Commands::Merge { date, force }) => {
+            let date = resolve(&date)?;
+            merge_notes(&config, date, force, cli.dry_run)?;
+            autocommit(&config, &format!("nn: merge notes for {}", date))?
+        }
+        Some(Commands::Move {
+            from,
+            to,
+            force,
+            yes,
+        }) => {
+            let (from, to) = (resolve(&from)?, resolve(&to)?);
+            move_note(&config, from, to, force, yes, cli.dry_run)?;
+            autocommit(&config, &format!("nn: move {} to {}", from, to))?
+        }
+        Some(Commands::Export { format, out }) => match format {
+            ExportFormat::Html => {
+                let exported = export_html(&config, &out)?;
+                println!("Exported {} notes to {}", exported, out.display());
+            }
+        },
+        Some(Commands::Find { query, fuzzy }) => find_notes(&config, &query, fuzzy)?,
+        #[cfg(feature = "fuzzy")]
+        Some(Commands::Fuzzy { query }) => {
+            let query = match query {
+                Some(query) => query,
+                None => {
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input)?;
+                    input.trim().to_string()
+                }
+            };
+            fuzzy_find_notes(&config, &query)?
+        }
+        Some(Commands::Grep {
+            pattern,
+            case_insensitive,
+            include_archived,
+            output,
+        }) => {
+            config.include_archived = include_archived;
+            let matcher = Matcher::new(&pattern, case_insensitive, true)
+                .map_err(|e| format!("invalid search regex: {}", e))?;
+            let color = config
+                .color
+                .enabled(output.is_none() && io::stdout().is_terminal());
+            let mut out = output_writer(output.as_deref())?;
+            if grep_notes(&config, &matcher, color, &mut out)? == 0 {
+                eprintln!("No matches for '{}'", pattern);
+            }
+        }
+        Some(Commands::Append { text }) => {
+            let text = append_text(&text)?;
+            if !text.trim().is_empty() {
+                let now = now.clone();
+                append_note(&config, &now, &text, cli.dry_run)?;
+                autocommit(&config, &format!("nn: append to {}", now.date()))?
+            }
+        }
+        Some(Commands::Attach { date, file }) => {
+            let date = resolve(&date)?;
+            attach_file(&config, date, &file, cli.dry_run)?;
+            autocommit(
+                &config,
+                &format!("nn: attach {} to {}", file.display(), date),
+            )?
+        }
+        Some(Commands::Backup { out }) => {
+            let out = match out {
+                Some(out) => out,
+                None => env::current_dir()?,
+            };
+            backup_notes(&config, &out, cli.dry_run)?
+        }
+        Some(Commands::Calendar { year }) => {
+            show_calendar(&config, year.unwrap_or_else(|| now.year()))?
+        }
+        Some(Commands::Backlinks { date }) => {
+            let date = resolve(&date)?;
+            show_backlinks(&config, date)?
+        }
+        Some(Commands::Links { date }) => {
+            let date = resolve(&date)?;
+            show_links(&config, date)?
+        }
+        Some(Commands::List {
+            json,
+            oldest_first,
+            paths,
+            include_archived,
+            limit,
+            range,
+        }) => {
+            config.include_archived = include_archived;
+            let range = range.resolve(now.date()).map_err(NnError::InvalidDate)?;
+            let pins = Pins::load(&pins_path)?;
+            list_notes(&config, &pins, json, oldest_first, paths, range, limit)?
+        }
+        Some(Commands::Import { path, date }) => {
+            let today = now.date();
+            if date.is_some() && path.is_dir() {
+                return Err("--date can only be used when importing a single file".into());
+            }
+            let date = match date {
+                Some(date) => resolve(&date)?,
+                None => today,
+            };
+            import_notes(&config, &path, date, cli.dry_run)?;
+            autocommit(&config, &format!("nn: import {}", path.display()))?
+        }
+        Some(Commands::Open) => open_notes_dir(&config, cli.dry_run)?,
+        Some(Commands::Pin { date }) => {
+            let date = resolve(&date)?;
+            set_pinned(&config, &pins_path, date, true, cli.dry_run)?
+        }
+        Some(Commands::Templates) => {
+            for name in config.available_templates()?.keys() {
+                println!("{}", name);
+            }
+        }
+        Some(Commands::Pinned) => {
+            for name in Pins::load(&pins_path)?.notes {
+                println!("{}", name);
+            }
+        }
+        Some(Commands::Unpin { date }) => {
+            let date = resolve(&date)?;
+            set_pinned(&config, &pins_path, date, false, cli.dry_run)?
+        }
+        Some(Commands::RenameTag { old, new, preview }) => {
+            rename_tag(&config, &old, &new, preview, cli.dry_run)?;
+            autocommit(&config, &format!("nn: rename tag {} to {}", old, new))?
+        }
+        Some(Commands::Recent { count, limit }) => {
+            recent_notes(&config, count.or(limit).unwrap_or(10))?
+        }
+        #[cfg(feature = "tui")]
+        Some(Commands::Tui) => run_tui(&config, cli.dry_run)?,
+        #[cfg(feature = "watch")]
+        Some(Commands::Watch {
+            date,
+            command,
+            export,
+        }) => {
+            let date = resolve(&date)?;
+            if command.is_some() {
+                config.after_edit = command;
+            }
+            watch_note(&config, date, export.as_deref(), cli.dry_run)?
+        }
+        Some(Commands::Scratch) => {
+            edit_scratch(&config, cli.dry_run)?;
+            autocommit(&config, "nn: update scratch")?
+        }
+        Some(Commands::New {
+            date,
+            template,
+            stdin,
+            append,
+            force,
+        }) => {
+            let date = match date {
+                Some(date) => resolve(&date)?,
+                None => now.date(),
+            };
+            if stdin {
+                let mut body = String::new();
+                io::stdin().read_to_string(&mut body)?;
+                write_note(&config, date, &body, append, force, cli.dry_run)?;
+            } else {
+                if let Some(name) = &template {
+                    config.use_template(name)?;
+                    if note_exists(&config, date) {
+                        eprintln!(
+                            "Warning: the note for {} already exists, template '{}' not applied",
+                            date, name
+                        );
+                    }
+                }
+                edit_note(&config, date, cli.dry_run)?;
+            }
+            autocommit(&config, &format!("nn: update {}", date))?
+        }
+        Some(Commands::Clean { yes }) => {
+            clean_notes(&config, yes, cli.dry_run)?;
+            autocommit(&config, "nn: clean empty notes")?
+        }
+        Some(Commands::Sync) => sync_notes(&config, cli.dry_run)?,
+        Some(Commands::OnThisDay { show }) => show_on_this_day(&config, now.date(), show)?,
+        Some(Commands::Random { edit, old }) => {
+            random_note(&config, edit, old, now.date(), cli.dry_run)?
+        }
+        Some(Commands::Diff { date, rev }) => diff_note(&config, resolve(&date)?, rev.as_deref())?,
+        Some(Commands::Index) => {
+            write_index(&config, cli.dry_run)?;
+            autocommit(&config, "nn: update index")?
+        }
+        Some(Commands::Search {
+            query,
+            case_insensitive,
+            regex,
+            context,
+            count,
+            files_only,
+            quiet,
+            include_archived,
+            output,
+            limit,
+            range,
+        }) => {
+            config.include_archived = include_archived;
+            let matcher = Matcher::new(&query, case_insensitive, regex)
+                .map_err(|e| format!("invalid search regex: {}", e))?;
+            let range = range.resolve(now.date()).map_err(NnError::InvalidDate)?;
+            let color = config
+                .color
+                .enabled(output.is_none() && io::stdout().is_terminal());
+            let mut out = output_writer(output.as_deref())?;
+            let total = if count {
+                count_matches(&config, &matcher, range, limit, &mut out)?
+            } else {
+                let mode = if quiet {
+                    SearchMode::Quiet
+                } else if files_only {
+                    SearchMode::Files
+                } else {
+                    SearchMode::Lines(context)
+                };
+                search_notes(&config, &matcher, mode, range, limit, color, &mut out)?
+            };
+            if total == 0 && !quiet {
+                eprintln!("No matches for '{}'", query);
+            }
+            if total == 0 && (quiet || files_only) {
+                return Err(NnError::NoMatch.into());
+            }
+        }
+        Some(Commands::Show { date, render }) => {
+            let date = resolve(&date)?;
+            show_note(&config, date, render)?
+        }
+        Some(Commands::Stats) => show_stats(&config)?,
+        Some(Commands::Count { what }) => println!("{}", count_total(&config, what)?),
+        Some(Commands::Info { date }) => show_info(&config, resolve(&date)?)?,
+        Some(Commands::WordCount { date }) => {
+            let date = date.map(|date| resolve(&date)).transpose()?;
+            word_count(&config, date)?
+        }
+        Some(Commands::Streak) => show_streak(&config)?,
+        Some(Commands::Tag { name }) => list_tagged_notes(&config, &name)?,
+        Some(Commands::Todos { all }) => list_todos(&config, all)?,
+        Some(Commands::Tags {
+            count,
+            format,
+            include_archived,
+        }) => {
+            config.include_archived = include_archived;
+            extract_tags(&config, count, format)?
+        }
+        Some(Commands::Yesterday) => {
+            // span arithmetic on the zoned time handles month and year boundaries
+            let yesterday = now.checked_sub(1.day())?.date();
+            edit_note(&config, yesterday, cli.dry_run)?;
+            autocommit(&config, &format!("nn: update {}", yesterday))?
+        }
+        None => {
+            // Default: edit today's note
+            let today = now.date();
+            if cli.no_create && !note_exists(&config, today) {
+                println!("No note for {} yet, not creating one", today);
+                return Ok(());
+            }
+            edit_note(&config, today, cli.dry_run)?;
+            autocommit(&config, &format!("nn: update {}", today))?
+        }
+    }
+    Ok(())
+}
+
+/// Split an alias definition into arguments at whitespace, keeping text in
+/// single or double quotes together
+fn split_alias(definition: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in definition.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    args
+}
+
+/// Replace a command-line subcommand that names one of `aliases` with the
+/// alias's arguments, repeatedly, so aliases can build on each other. Built-in
+/// subcommands always win over aliases of the same name, and an alias that
+/// leads back to itself is an error.
+fn expand_aliases(
+    mut args: Vec<std::ffi::OsString>,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<std::ffi::OsString>, String> {
+    let command = Cli::command();
+    let builtins: HashSet<&str> = command
+        .get_subcommands()
+        .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_all_aliases()))
+        .chain(["help"])
+        .collect();
+    // global options that take a value, so the value isn't mistaken for the subcommand
+    let valued: HashSet<String> = command
+        .get_arguments()
+        .filter(|arg| arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            long.into_iter().chain(short)
+        })
+        .collect();
+
+    let mut chain: Vec<String> = Vec::new();
+    loop {
+        let mut i = 1;
+        while let Some(arg) = args.get(i).and_then(|arg| arg.to_str()) {
+            if arg == "--" || !arg.starts_with('-') {
+                break;
+            }
+            i += if valued.contains(arg) { 2 } else { 1 };
+        }
+        let Some(name) = args.get(i).and_then(|arg| arg.to_str()) else {
+            return Ok(args);
+        };
+        if builtins.contains(name) {
+            return Ok(args);
+        }
+        let Some(definition) = aliases.get(name) else {
+            return Ok(args);
+        };
+        if chain.iter().any(|seen| seen == name) {
+            chain.push(name.to_string());
+            return Err(format!("alias loop: {}", chain.join(" -> ")));
+        }
+        chain.push(name.to_string());
+        args.splice(i..=i, split_alias(definition).into_iter().map(Into::into));
+    }
+}
+
+/// The aliases from the config file `args` would use, or none if it can't be
+/// read; `run` reports config problems properly later
+fn config_aliases(args: &[std::ffi::OsString]) -> BTreeMap<String, String> {
+    let flag = args.iter().enumerate().find_map(|(i, arg)| {
+        let arg = arg.to_str()?;
+        match arg.strip_prefix("--config") {
+            Some("") => args.get(i + 1).map(PathBuf::from),
+            Some(path) => path.strip_prefix('=').map(PathBuf::from),
+            None => None,
+        }
+    });
+    config_file(flag)
+        .and_then(fs::read_to_string)
+        .ok()
+        .and_then(|toml_str| parse_config(&toml_str).ok())
+        .map(|(config, _)| config.aliases)
+        .unwrap_or_default()
+}
+
+fn main() {
+    // answer dynamic completion requests from the shell, if this is one
+    CompleteEnv::with_factory(Cli::command).complete();
+
+    let args: Vec<_> = env::args_os().collect();
+    let aliases = config_aliases(&args);
+    let args = expand_aliases(args, &aliases).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(NnError::Config(e).exit_code());
+    });
+    // usage errors exit with 1 rather than clap's 2, which means "not found" here
+    let cli = Cli::try_parse_from(args).unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+    VERBOSITY.store(cli.verbose, atomic::Ordering::Relaxed);
+    if let Err(e) = run(cli) {
+        if !matches!(e.downcast_ref(), Some(NnError::NoMatch)) {
+            eprintln!("error: {}", e);
+        }
+        std::process::exit(exit_code(e.as_ref()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_get_note_path() {
+        let config = Config {
+            notes_dir: PathBuf::from("/tmp/my-notes"),
+            editor: "nano".to_string(),
+            ..Config::new(PathBuf::new())
+        };
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        assert_eq!(path, PathBuf::from("/tmp/my-notes/2025-04-09.md"));
+    }
+
+    #[test]
+    fn test_config_editor_args_optional() {
+        let old: Config = toml::from_str("notes_dir = \"/notes\"\neditor = \"vim\"\n").unwrap();
+        assert!(old.editor_args.is_empty());
+
+        let new: Config = toml::from_str(
+            "notes_dir = \"/notes\"\neditor = \"code\"\neditor_args = [\"--wait\"]\n",
+        )
+        .unwrap();
+        assert_eq!(new.editor_args, vec!["--wait"]);
+    }
+
+    #[test]
+    fn test_editor_command_precedence() {
+        let config = Config {
+            editor: "vim".into(),
+            editor_args: vec!["+startinsert".into()],
+            ..Config::new(PathBuf::new())
+        };
+        let env = |vars: &'static [(&str, &str)]| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        let both = env(&[("VISUAL", "code --wait"), ("EDITOR", "emacs")]);
+        assert_eq!(editor_command(&config, both), vec!["code", "--wait"]);
+        let editor_only = env(&[("EDITOR", "emacs")]);
+        assert_eq!(editor_command(&config, editor_only), vec!["emacs"]);
+        assert_eq!(
+            editor_command(&config, env(&[])),
+            vec!["vim", "+startinsert"]
+        );
+
+        let unset = Config {
+            editor: String::new(),
+            ..Config::new(PathBuf::new())
+        };
+        assert_eq!(editor_command(&unset, env(&[])), vec!["nano"]);
+
+        let flagged = Config {
+            editor_override: Some("gedit -s".to_string()),
+            ..Config::new(PathBuf::new())
+        };
+        let both = env(&[("VISUAL", "code --wait"), ("EDITOR", "emacs")]);
+        assert_eq!(editor_command(&flagged, both), vec!["gedit", "-s"]);
+    }
+
+    #[test]
+    fn test_override_notes_dir_precedence() {
+        let dir = tempdir().unwrap();
+        let flag = dir.path().join("from-flag");
+        let env_value = dir.path().join("from-env");
+        let mut config = Config::new(dir.path().join("configured"));
+
+        override_notes_dir(&mut config, None, None).unwrap();
+        assert_eq!(config.notes_dir, dir.path().join("configured"));
+
+        override_notes_dir(&mut config, None, Some(env_value.clone())).unwrap();
+        assert_eq!(config.notes_dir, env_value);
+
+        override_notes_dir(&mut config, Some(flag.clone()), Some(env_value)).unwrap();
+        assert_eq!(config.notes_dir, flag);
+        assert!(flag.is_dir());
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let dir = tempdir().unwrap();
+        let toml_str = format!(
+            "notes_dir = \"{0}/personal\"\neditor = \"nano\"\ndefault_profile = \"work\"\n\n\
+             [profiles.work]\nnotes_dir = \"{0}/work\"\neditor = \"vim\"\n\n\
+             [profiles.journal]\nnotes_dir = \"{0}/journal\"\n",
+            dir.path().display()
+        );
+        let base: Config = toml::from_str(&toml_str).unwrap();
+
+        let mut config: Config = toml::from_str(&toml_str).unwrap();
+        config.apply_profile(None).unwrap();
+        assert_eq!(config.notes_dir, dir.path().join("work"));
+        assert_eq!(config.editor, "vim");
+        assert!(config.notes_dir.is_dir());
+
+        let mut config: Config = toml::from_str(&toml_str).unwrap();
+        config.apply_profile(Some("journal")).unwrap();
+        assert_eq!(config.notes_dir, dir.path().join("journal"));
+        assert_eq!(config.editor, base.editor);
+
+        let mut config: Config = toml::from_str(&toml_str).unwrap();
+        assert!(config.apply_profile(Some("missing")).is_err());
+
+        // the default config has no profiles and round-trips through toml
+        let mut default = Config::new(dir.path().to_path_buf());
+        default.apply_profile(None).unwrap();
+        assert!(toml::from_str::<Config>(&toml::to_string(&default).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_date_format_with_subdirectories() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            date_format: "%Y/%m/%d".into(),
+            ..Config::new(dir.path().to_path_buf())
+        };
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        assert_eq!(path, dir.path().join("2025/04/09.md"));
+
+        create_note_if_missing(&config, &path, date).unwrap();
+        let paths = note_paths(&config).unwrap();
+        assert_eq!(paths, vec![path.clone()]);
+        assert_eq!(note_name(&config, &path), "2025/04/09");
+        assert_eq!(note_date(&config, &path), Some(date));
+    }
+
+    #[test]
+    fn test_parse_date_rejects_malformed_dates() {
+        assert_eq!(
+            parse_date("2025-04-09").unwrap(),
+            jiff::civil::date(2025, 4, 9)
+        );
+        assert!(parse_date("2025-13-40").is_err());
+        assert!(parse_date("yesterday").is_err());
+        assert!(parse_date("2025-01-01T10:00").is_err());
+        assert!(parse_date("../../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_resolve_date_relative_forms() {
+        let today = jiff::civil::date(2025, 3, 1);
+        assert_eq!(resolve_date("today", today).unwrap(), today);
+        assert_eq!(
+            resolve_date("yesterday", today).unwrap(),
+            jiff::civil::date(2025, 2, 28)
+        );
+        assert_eq!(
+            resolve_date("-1", today).unwrap(),
+            jiff::civil::date(2025, 2, 28)
+        );
+        assert_eq!(
+            resolve_date("-7d", today).unwrap(),
+            jiff::civil::date(2025, 2, 22)
+        );
+        assert_eq!(
+            resolve_date("+1w", today).unwrap(),
+            jiff::civil::date(2025, 3, 8)
+        );
+        assert_eq!(
+            resolve_date("2024-12-25", today).unwrap(),
+            jiff::civil::date(2024, 12, 25)
+        );
+        assert!(resolve_date("-99999999d", today).is_err());
+        assert!(resolve_date("+1x", today).is_err());
+    }
+
+    #[test]
+    fn test_delete_note_with_yes_skips_prompt() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        fs::write(&path, "bye").unwrap();
+
+        delete_note(&config, date, true, false, false, false).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_dry_run_leaves_files_untouched() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        fs::write(&path, "#wip").unwrap();
+
+        delete_note(&config, date, true, false, false, true).unwrap();
+        delete_note(&config, date, true, true, false, true).unwrap();
+        rename_tag(&config, "wip", "done", false, true).unwrap();
+        rename_tag(&config, "wip", "done", true, false).unwrap();
+        edit_note(&config, jiff::civil::date(2025, 4, 10), true).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#wip");
+        assert_eq!(note_paths(&config).unwrap(), vec![path]);
+    }
+
+    #[test]
+    fn test_delete_note_archive_adds_suffix_on_collision() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+
+        fs::write(&path, "first").unwrap();
+        delete_note(&config, date, false, true, false, false).unwrap();
+        fs::write(&path, "second").unwrap();
+        delete_note(&config, date, false, true, false, false).unwrap();
+
+        let archive = config.archive_dir();
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(archive.join("2025-04-09.md")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(archive.join("2025-04-09-1.md")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_delete_note_handles_encrypted_only_note() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+        let encrypted = encrypted_path(&get_note_path(&config, date));
+
+        fs::write(&encrypted, "first").unwrap();
+        delete_note(&config, date, false, true, false, false).unwrap();
+        fs::write(&encrypted, "second").unwrap();
+        delete_note(&config, date, false, true, false, false).unwrap();
+        let archive = config.archive_dir();
+        assert!(archive.join("2025-04-09.md.age").exists());
+        assert!(archive.join("2025-04-09-1.md.age").exists());
+
+        fs::write(&encrypted, "third").unwrap();
+        delete_note(&config, date, true, false, false, false).unwrap();
+        assert!(!encrypted.exists());
+        assert!(delete_note(&config, date, true, false, false, false).is_err());
+    }
+
+    #[test]
+    fn test_move_note_keeps_encrypted_note_encrypted() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let (from, to) = (
+            jiff::civil::date(2025, 4, 9),
+            jiff::civil::date(2025, 4, 10),
+        );
+        let source = encrypted_path(&get_note_path(&config, from));
+        fs::write(&source, "secret").unwrap();
+
+        move_note(&config, from, to, false, true, false).unwrap();
+        let target = encrypted_path(&get_note_path(&config, to));
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "secret");
+        assert_eq!(note_name(&config, &target), "2025-04-10");
+
+        // a plain note for the day may not end up beside the encrypted one
+        fs::write(get_note_path(&config, from), "plain").unwrap();
+        assert!(move_note(&config, from, to, true, true, false).is_err());
+        assert!(get_note_path(&config, from).exists());
+    }
+
+    #[test]
+    fn test_read_note_reports_missing_note() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+
+        let err = read_note(&config, date).unwrap_err();
+        assert_eq!(err.to_string(), "no note found for 2025-04-09");
+        fs::write(get_note_path(&config, date), "hello").unwrap();
+        assert_eq!(read_note(&config, date).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_render_markdown_ansi() {
+        let note = "# Title\n\n- **done** with `cargo`\n```\nfn main() {}\n```\n";
+        assert_eq!(
+            render_markdown_ansi(note, false),
+            "Title\n\n• done with cargo\n    fn main() {}\n"
+        );
+        let colored = render_markdown_ansi(note, true);
+        assert!(colored.starts_with("\x1b[1;35mTitle\x1b[0m\n"));
+        assert!(colored.contains("\x1b[1mdone\x1b[0m"));
+    }
+
+    #[test]
+    fn test_date_range_retains_dated_notes_within_bounds() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let mut paths: Vec<PathBuf> = [
+            "2025-01-01.md",
+            "2025-01-05.md",
+            "2025-01-09.md",
+            "ideas.md",
+        ]
+        .iter()
+        .map(|name| dir.path().join(name))
+        .collect();
+        let range = DateRangeArgs {
+            from: Some("2025-01-05".to_string()),
+            to: None,
+        }
+        .resolve(jiff::civil::date(2025, 1, 10))
+        .unwrap();
+        range.retain(&config, &mut paths);
+        assert_eq!(
+            paths,
+            vec![
+                dir.path().join("2025-01-05.md"),
+                dir.path().join("2025-01-09.md")
+            ]
+        );
+
+        let reversed = DateRangeArgs {
+            from: Some("today".to_string()),
+            to: Some("-1d".to_string()),
+        };
+        assert!(reversed.resolve(jiff::civil::date(2025, 1, 10)).is_err());
+    }
+
+    #[test]
+    fn test_retitle_heading_matches_old_name() {
+        let note = "---\ndate: 2025-04-09\n---\n\n# 2025-04-09.md\n\nbody\n";
+        assert_eq!(
+            retitle_heading(note, "# ", "2025-04-09", "2025-04-10").as_deref(),
+            Some("---\ndate: 2025-04-09\n---\n\n# 2025-04-10.md\n\nbody\n")
+        );
+        assert_eq!(
+            retitle_heading("# 2025-04-09\n", "# ", "2025-04-09", "2025-04-10").as_deref(),
+            Some("# 2025-04-10\n")
+        );
+        assert_eq!(
+            retitle_heading("# Trip\n", "# ", "2025-04-09", "2025-04-10"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_links_extracts_targets() {
+        let note = "See [[2025-04-01]] and [[projects/nn|the nn note]].\n\
+                    Plans in [[ 2025-04-02#Plans ]], not [single] or [[]].";
+        assert_eq!(
+            find_links(note).collect::<Vec<_>>(),
+            ["2025-04-01", "projects/nn", "2025-04-02"]
+        );
+    }
+
+    #[test]
+    fn test_backlink_index_maps_targets_to_sources() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-02.md"), "after [[2025-04-01]]\n").unwrap();
+        fs::write(
+            dir.path().join("2025-04-03.md"),
+            "[[2025-04-01]] [[2025-04-02]]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("2025-04-01.md"), "no links\n").unwrap();
+
+        let index = backlink_index(&config).unwrap();
+        assert_eq!(
+            index["2025-04-01"].iter().collect::<Vec<_>>(),
+            ["2025-04-02", "2025-04-03"]
+        );
+        assert_eq!(index["2025-04-02"].len(), 1);
+        assert!(!index.contains_key("2025-04-03"));
+    }
+
+    #[test]
+    fn test_set_pinned_round_trips_through_pin_store() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().join("notes"));
+        let pins_path = Pins::path(dir.path());
+        let date = jiff::civil::date(2025, 4, 9);
+        assert!(set_pinned(&config, &pins_path, date, true, false).is_err());
+
+        fs::create_dir_all(&config.notes_dir).unwrap();
+        fs::write(get_note_path(&config, date), "# pinned\n").unwrap();
+        set_pinned(&config, &pins_path, date, true, true).unwrap();
+        assert!(!pins_path.exists());
+        set_pinned(&config, &pins_path, date, true, false).unwrap();
+        let pins = Pins::load(&pins_path).unwrap();
+        assert_eq!(pins.notes.iter().collect::<Vec<_>>(), ["2025-04-09"]);
+
+        set_pinned(&config, &pins_path, date, false, false).unwrap();
+        assert!(Pins::load(&pins_path).unwrap().notes.is_empty());
+    }
+
+    #[test]
+    fn test_append_note_adds_timestamped_bullet() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let now: Zoned = "2025-04-09T14:05:00[UTC]".parse().unwrap();
+        append_note(&config, &now, "call the bank", false).unwrap();
+        append_note(&config, &now, "second", false).unwrap();
+        let contents = fs::read_to_string(dir.path().join("2025-04-09.md")).unwrap();
+        assert_eq!(
+            contents,
+            "# 2025-04-09\n\n- 14:05 call the bank\n- 14:05 second\n"
+        );
+        assert_eq!(
+            append_entry("no newline", "09:00", "x"),
+            "no newline\n- 09:00 x\n"
+        );
+        assert_eq!(
+            append_entry("", "09:00", "piped\n\nfrom stdin\n"),
+            "- 09:00 piped\n\n  from stdin\n"
+        );
+    }
+
+    #[test]
+    fn test_open_dir_command_prefers_workspace_editors() {
+        let config = Config::new(PathBuf::from("/notes"));
+        let env =
+            |value: &'static str| move |var: &str| (var == "VISUAL").then(|| value.to_string());
+        assert_eq!(
+            open_dir_command(&config, env("/usr/bin/code --wait")),
+            ["/usr/bin/code"]
+        );
+        let fallback = open_dir_command(&config, env("vim"));
+        assert_ne!(fallback, ["vim"]);
+        assert!(["open", "cmd", "xdg-open"].contains(&fallback[0].as_str()));
+    }
+
+    #[test]
+    fn test_parse_config_reports_unknown_keys() {
+        let toml_str = "notes_dir = \"/notes\"\neditor = \"vim\"\nnoes_dir = \"/typo\"\n\n\
+                        [profiles.work]\nnotes_dir = \"/work\"\neditr = \"code\"\n";
+        let (config, unknown) = parse_config(toml_str).unwrap();
+        assert_eq!(config.notes_dir, PathBuf::from("/notes"));
+        assert_eq!(unknown, ["noes_dir", "profiles.work.editr"]);
+        assert!(parse_config("notes_dir = 1").is_err());
+    }
+
+    #[test]
+    fn test_expand_path_home_and_variables() {
+        let home = Path::new("/home/me");
+        let env = |var: &str| (var == "NOTES").then(|| "/srv/notes".to_string());
+        assert_eq!(expand_path(Path::new("~"), Some(home), env), home);
+        assert_eq!(
+            expand_path(Path::new("~/sub/notes"), Some(home), env),
+            home.join("sub/notes")
+        );
+        assert_eq!(
+            expand_path(Path::new("~other/notes"), Some(home), env),
+            PathBuf::from("~other/notes")
+        );
+        assert_eq!(
+            expand_path(Path::new("$NOTES/daily"), Some(home), env),
+            PathBuf::from("/srv/notes/daily")
+        );
+        assert_eq!(
+            expand_path(Path::new("${NOTES}2/$UNSET"), Some(home), env),
+            PathBuf::from("/srv/notes2/$UNSET")
+        );
+    }
+
+    #[test]
+    fn test_load_or_init_config_creates_default_on_first_run() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("nn/config.toml");
+        let config = load_or_init_config_with(&config_file, None).unwrap();
+        assert_eq!(config.notes_dir, dir.path().join("nn/notes"));
+        assert!(config.notes_dir.is_dir());
+        let written = fs::read_to_string(&config_file).unwrap();
+        assert_eq!(parse_config(&written).unwrap().0.editor, "nano");
+    }
+
+    #[test]
+    fn test_load_or_init_config_reads_existing_file() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("config.toml");
+        fs::write(
+            &config_file,
+            "notes_dir = \"/srv/notes\"\neditor = \"vim\"\ndate_format = \"%Y/%m/%d\"\n",
+        )
+        .unwrap();
+        let config = load_or_init_config_with(&config_file, None).unwrap();
+        assert_eq!(config.notes_dir, PathBuf::from("/srv/notes"));
+        assert_eq!(config.editor, "vim");
+        assert_eq!(config.date_format, "%Y/%m/%d");
+    }
+
+    #[test]
+    fn test_load_or_init_config_recovers_from_malformed_file() {
+        let dir = tempdir().unwrap();
+        let config_file = dir.path().join("config.toml");
+        fs::write(&config_file, "notes_dir = [not toml").unwrap();
+
+        assert!(load_or_init_config_with(&config_file, None).is_err());
+        assert!(load_or_init_config_with(&config_file, Some(&|_| Ok(false))).is_err());
+        let config = load_or_init_config_with(&config_file, Some(&|_| Ok(true))).unwrap();
+        assert_eq!(config.notes_dir, dir.path().join("notes"));
+        // the broken file is left for the user to fix
+        assert_eq!(
+            fs::read_to_string(&config_file).unwrap(),
+            "notes_dir = [not toml"
+        );
+    }
+
+    #[test]
+    fn test_import_notes_maps_files_to_dates_and_appends() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().join("notes"));
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("2025-04-09.md"), "imported\n").unwrap();
+        fs::write(outside.join("2025-04-10.md"), "fresh\n").unwrap();
+        fs::write(outside.join("ideas.md"), "undated\n").unwrap();
+        fs::create_dir_all(&config.notes_dir).unwrap();
+        fs::write(config.notes_dir.join("2025-04-09.md"), "# existing").unwrap();
+
+        let today = jiff::civil::date(2025, 5, 1);
+        import_notes(&config, &outside, today, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.notes_dir.join("2025-04-09.md")).unwrap(),
+            "# existing\n\n---\n\nimported\n"
+        );
+        assert_eq!(
+            fs::read_to_string(config.notes_dir.join("2025-04-10.md")).unwrap(),
+            "fresh\n"
+        );
+        assert!(!config.notes_dir.join("2025-05-01.md").exists());
+
+        import_notes(&config, &outside.join("ideas.md"), today, false).unwrap();
+        assert!(config.notes_dir.join("2025-05-01.md").exists());
+    }
+
+    #[test]
+    fn test_write_backup_archives_notes_dir() {
+        let dir = tempdir().unwrap();
+        let notes_dir = dir.path().join("notes");
+        fs::create_dir_all(notes_dir.join("2025")).unwrap();
+        fs::write(notes_dir.join("2025/04-09.md"), "# note\n").unwrap();
+        let target = dir.path().join("backup.tar.gz");
+        write_backup(&notes_dir, &target).unwrap();
+
+        let file = fs::File::open(&target).unwrap();
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let names: Vec<PathBuf> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert!(names.contains(&PathBuf::from("notes/2025/04-09.md")));
+
+        let now: Zoned = "2025-04-09T15:30:00[UTC]".parse().unwrap();
+        assert_eq!(backup_file_name(&now), "notes-backup-20250409-1530.tar.gz");
+    }
+
+    #[test]
+    fn test_backup_notes_refuses_target_inside_notes_dir() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().join("notes"));
+        fs::create_dir_all(&config.notes_dir).unwrap();
+
+        let sneaky = dir.path().join("elsewhere/../notes/backups");
+        assert!(backup_notes(&config, &sneaky, true).is_err());
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&config.notes_dir, &link).unwrap();
+            assert!(backup_notes(&config, &link.join("backups"), true).is_err());
+        }
+        assert!(backup_notes(&config, &dir.path().join("backups"), true).is_ok());
+    }
+
+    #[test]
+    fn test_encrypt_contents_round_trips_with_passphrase() {
+        let mut recipient = age::scrypt::Recipient::new("correct horse".to_string().into());
+        // keep the key derivation cheap in tests
+        recipient.set_work_factor(4);
+        let encrypted = encrypt_contents(b"# secret\n", &recipient).unwrap();
+        assert!(!encrypted.windows(6).any(|window| window == b"secret"));
+
+        let decrypted = decrypt_contents(&encrypted, "correct horse".to_string().into()).unwrap();
+        assert_eq!(decrypted, "# secret\n");
+        assert!(decrypt_contents(&encrypted, "wrong".to_string().into()).is_err());
+        assert_eq!(
+            encrypted_path(Path::new("/notes/2025-04-09.md")),
+            PathBuf::from("/notes/2025-04-09.md.age")
+        );
+    }
+
+    #[test]
+    fn test_month_grid_marks_days_with_notes() {
+        let words = HashMap::from([(jiff::civil::date(2025, 2, 3), 12)]);
+        let grid = month_grid(2025, 2, &words, false);
+        assert_eq!(grid[0], "February      ");
+        assert_eq!(grid[1], "M T W T F S S ");
+        // February 2025 starts on a Saturday
+        assert_eq!(grid[2], "          · · ");
+        assert_eq!(grid[3], "█ · · · · · · ");
+        assert_eq!(grid[6], "· · · · ·     ");
+        assert_eq!(grid[7], "              ");
+    }
+
+    #[test]
+    fn test_merge_notes_combines_same_day_files() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(
+            dir.path().join("2025-04-09.md"),
+            "# 2025-04-09\n\nmorning\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("2025-04-09-1.md"), "imported\n").unwrap();
+        fs::write(dir.path().join("2025-04-09 call.md"), "call notes").unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "next day\n").unwrap();
+
+        let date = jiff::civil::date(2025, 4, 9);
+        merge_notes(&config, date, false, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("2025-04-09.md")).unwrap(),
+            "# 2025-04-09\n\nmorning\n\n## 2025-04-09 call\n\ncall notes\n\n\
+             ## 2025-04-09-1\n\nimported\n"
+        );
+        assert!(dir.path().join("2025-04-09-1.md").exists());
+
+        merge_notes(&config, date, true, false).unwrap();
+        assert!(!dir.path().join("2025-04-09-1.md").exists());
+        assert!(dir.path().join("2025-04-10.md").exists());
+    }
+
+    #[test]
+    fn test_merge_notes_keeps_unreadable_canonical_note() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let canonical = dir.path().join("2025-04-09.md");
+        fs::write(&canonical, b"# 2025-04-09\n\xff\n").unwrap();
+        fs::write(dir.path().join("2025-04-09-1.md"), "imported\n").unwrap();
+
+        let date = jiff::civil::date(2025, 4, 9);
+        assert!(merge_notes(&config, date, true, false).is_err());
+        assert_eq!(fs::read(&canonical).unwrap(), b"# 2025-04-09\n\xff\n");
+        assert!(dir.path().join("2025-04-09-1.md").exists());
+    }
+
+    #[test]
+    fn test_note_paths_skip_archive_and_hidden_files() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::new(dir.path().to_path_buf());
+        fs::create_dir_all(dir.path().join("archive")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("2025-04-09.md"), "kept").unwrap();
+        fs::write(dir.path().join("archive/2025-01-01.md"), "archived").unwrap();
+        fs::write(dir.path().join(".git/HEAD.md"), "hidden").unwrap();
+        fs::write(dir.path().join(".draft.md"), "hidden").unwrap();
+
+        assert_eq!(
+            note_paths(&config).unwrap(),
+            [dir.path().join("2025-04-09.md")]
+        );
+        config.include_archived = true;
+        let mut paths = note_paths(&config).unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                dir.path().join("2025-04-09.md"),
+                dir.path().join("archive/2025-01-01.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_tags_ignores_non_markdown_files() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "#work").unwrap();
+        fs::write(dir.path().join("notes.lock"), "#locked").unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"\xff\xd8#exif\x00").unwrap();
+
+        let counts = count_tags(&config).unwrap();
+        assert_eq!(counts, HashMap::from([("#work".to_string(), 1)]));
+    }
+
+    #[test]
+    fn test_scratch_note_is_left_out_of_listed_notes() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "").unwrap();
+        fs::write(scratch_path(&config), "# Scratch\n").unwrap();
+
+        assert_eq!(
+            listed_note_paths(&config).unwrap(),
+            [dir.path().join("2025-04-09.md")]
+        );
+        assert_eq!(note_paths(&config).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_format_tags_as_tsv_and_csv() {
+        let counts = HashMap::from([("#rust".to_string(), 3), ("#a,b".to_string(), 1)]);
+        assert_eq!(
+            format_tags(counts.clone(), true, TagFormat::Tsv),
+            ["#rust\t3", "#a,b\t1"]
+        );
+        assert_eq!(
+            format_tags(counts.clone(), true, TagFormat::Csv),
+            ["tag,count", "#rust,3", "\"#a,b\",1"]
+        );
+        assert_eq!(
+            format_tags(counts.clone(), true, TagFormat::Plain),
+            ["#rust  3", "#a,b   1"]
+        );
+        assert_eq!(
+            format_tags(counts, false, TagFormat::Plain),
+            ["#a,b", "#rust"]
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_is_note_change_matches_writes_to_the_note() {
+        use notify::{event::ModifyKind, Event, EventKind};
+
+        let path = Path::new("/notes/2025-04-09.md");
+        let write = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(PathBuf::from("/private/notes/2025-04-09.md"));
+        assert!(is_note_change(&write, path));
+        let other = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(PathBuf::from("/notes/2025-04-10.md"));
+        assert!(!is_note_change(&other, path));
+        let access = Event::new(EventKind::Access(notify::event::AccessKind::Any))
+            .add_path(path.to_path_buf());
+        assert!(!is_note_change(&access, path));
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_tui_filters_by_query_and_tag() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-08.md"), "release #work").unwrap();
+        fs::write(dir.path().join("2025-04-09.md"), "groceries #home").unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "retro #work").unwrap();
+
+        let mut app = TuiApp::load(&config).unwrap();
+        assert_eq!(app.selected().unwrap().name, "2025-04-10");
+        app.tag = Some("#work".to_string());
+        app.refilter();
+        assert_eq!(app.visible.len(), 2);
+        app.query = "RELEASE".to_string();
+        app.refilter();
+        assert_eq!(app.selected().unwrap().name, "2025-04-08");
+        app.query = "nothing".to_string();
+        app.refilter();
+        assert!(app.selected().is_none());
+    }
+
+    #[test]
+    fn test_config_now_uses_configured_timezone() {
+        let config = Config {
+            timezone: Some("Asia/Tokyo".to_string()),
+            ..Config::new(PathBuf::new())
+        };
+        let now = config.now().unwrap();
+        assert_eq!(now.time_zone().iana_name(), Some("Asia/Tokyo"));
+
+        let unknown = Config {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..Config::new(PathBuf::new())
+        };
+        assert!(unknown.now().is_err());
+    }
+
+    #[test]
+    fn test_check_date_format_rejects_time_specifiers() {
+        assert!(Config::new(PathBuf::new()).check_date_format().is_ok());
+        let hourly = Config {
+            date_format: "%Y-%m-%d-%H".to_string(),
+            ..Config::new(PathBuf::new())
+        };
+        assert!(hourly.check_date_format().is_err());
+    }
+
+    #[test]
+    fn test_find_todos_reads_task_list_items() {
+        let note = "# Tasks\n- [ ] reply to Sam\n  * [x] book flights\n- [] not a task\n- plain\n";
+        assert_eq!(
+            find_todos(note),
+            [
+                Todo {
+                    line: 2,
+                    done: false,
+                    text: "reply to Sam".to_string(),
+                },
+                Todo {
+                    line: 3,
+                    done: true,
+                    text: "book flights".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complete_todo_only_ticks_open_tasks() {
+        let note = "# Tasks\r\n- [ ] reply to Sam\r\n- [x] book flights\r\nplain\r\n";
+        assert_eq!(
+            complete_todo(note, 2).as_deref(),
+            Some("# Tasks\r\n- [x] reply to Sam\r\n- [x] book flights\r\nplain\r\n")
+        );
+        assert_eq!(complete_todo(note, 3), None);
+        assert_eq!(complete_todo(note, 4), None);
+        assert_eq!(complete_todo(note, 0), None);
+        assert_eq!(complete_todo(note, 9), None);
+    }
+
+    #[test]
+    fn test_search_notes_writes_to_output_file() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "# a\nrust here\nother\n").unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "# b\nnothing\n").unwrap();
+        let matcher = Matcher::new("rust", false, false).unwrap();
+        let range = DateRange {
+            from: None,
+            to: None,
+        };
+
+        let report = dir.path().join("out").join("report.txt");
+        assert!(output_writer(Some(&report)).is_err());
+        let report = dir.path().join("report.txt");
+        let mut out = output_writer(Some(&report)).unwrap();
+        let total = search_notes(
+            &config,
+            &matcher,
+            SearchMode::Lines(0),
+            range,
+            None,
+            false,
+            &mut out,
+        )
+        .unwrap();
+        drop(out);
+
+        assert_eq!(total, 1);
+        let written = fs::read_to_string(&report).unwrap();
+        assert!(written.ends_with(":\n2:rust here\n"));
+        assert!(!written.contains("2025-04-10"));
+    }
+
+    #[test]
+    fn test_search_notes_output_goes_to_writer() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let path = dir.path().join("2025-04-09.md");
+        fs::write(&path, "# a\nbefore\nrust here\nafter\n").unwrap();
+        let matcher = Matcher::new("rust", false, false).unwrap();
+
+        let mut stdout = Vec::new();
+        search_notes(
+            &config,
+            &matcher,
+            SearchMode::Lines(1),
+            DateRange::default(),
+            None,
+            false,
+            &mut stdout,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!("{}:\n2-before\n3:rust here\n4-after\n", path.display())
+        );
+    }
+
+    #[test]
+    fn test_count_matches_per_note_and_total() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let first = dir.path().join("2025-04-09.md");
+        fs::write(&first, "# a\nRust\nrust again\n").unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "# b\nnothing\n").unwrap();
+        let matcher = Matcher::new("rust", true, false).unwrap();
+
+        let mut out = Vec::new();
+        let total = count_matches(&config, &matcher, DateRange::default(), None, &mut out).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}: 2\ntotal: 2\n", first.display())
+        );
+    }
+
+    #[test]
+    fn test_glob_regex_matches_note_names() {
+        let year = glob_regex("2024-*").unwrap();
+        assert!(year.is_match("2024-01-05"));
+        assert!(!year.is_match("2025-01-05"));
+        assert!(!year.is_match("x2024-01-05"));
+        let months = glob_regex("2024-0[1-3]-?5").unwrap();
+        assert!(months.is_match("2024-02-15"));
+        assert!(!months.is_match("2024-04-15"));
+        assert!(glob_regex("2024-0[!1]-*").unwrap().is_match("2024-02-01"));
+        assert!(glob_regex("a.b").unwrap().is_match("a.b"));
+        assert!(!glob_regex("a.b").unwrap().is_match("axb"));
+    }
+
+    #[test]
+    fn test_glob_regex_rejects_malformed_classes() {
+        let error = glob_regex("2024-[01").unwrap_err();
+        assert_eq!(
+            error,
+            "invalid glob '2024-[01': unterminated '[' character class"
+        );
+        assert!(glob_regex("2024-[]").unwrap_err().contains("empty"));
+        assert!(glob_regex("2024-[!]").unwrap_err().contains("empty"));
+        assert!(glob_regex("[z-a]").unwrap_err().contains("malformed"));
+    }
+
+    #[test]
+    fn test_delete_notes_by_glob_and_range() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        for name in ["2024-01-01", "2024-06-01", "2025-01-01"] {
+            fs::write(dir.path().join(format!("{}.md", name)), "# n\n").unwrap();
+        }
+        let glob = glob_regex("2024-*").unwrap();
+        let paths = matching_notes(&config, Some(&glob), DateRange::default()).unwrap();
+        assert_eq!(paths.len(), 2);
+
+        // dry run leaves everything in place
+        delete_notes(&config, &paths, true, false, false, true).unwrap();
+        assert!(dir.path().join("2024-01-01.md").exists());
+
+        let range = DateRange {
+            from: Some(jiff::civil::date(2024, 3, 1)),
+            to: None,
+        };
+        let paths = matching_notes(&config, Some(&glob), range).unwrap();
+        assert_eq!(paths, vec![dir.path().join("2024-06-01.md")]);
+        delete_notes(&config, &paths, true, false, false, false).unwrap();
+        assert!(!dir.path().join("2024-06-01.md").exists());
+        assert!(dir.path().join("2024-01-01.md").exists());
+        assert!(dir.path().join("2025-01-01.md").exists());
+    }
+
+    #[test]
+    fn test_note_info_rows() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("2025-04-09.md");
+        let contents = "---\nmood: good\ntags: [work]\n---\n# Title\nsome words #rust\n";
+        fs::write(&path, contents).unwrap();
+
+        let rows = note_info(&path, contents, &TimeZone::UTC).unwrap();
+        let get = |label: &str| {
+            rows.iter()
+                .find(|(l, _)| l == label)
+                .map(|(_, v)| v.as_str())
+                .unwrap()
+        };
+        assert_eq!(get("Path"), path.display().to_string());
+        assert_eq!(get("Size"), format!("{} B", contents.len()));
+        assert_eq!(get("Lines"), "6");
+        assert_eq!(get("Tags"), "#rust #work");
+        assert_eq!(get("mood"), "good");
+        assert_eq!(get("tags"), "[work]");
+        assert_ne!(get("Modified"), "-");
+    }
+
+    #[test]
+    fn test_build_index_groups_by_year_and_month() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "# Planning\n").unwrap();
+        fs::write(dir.path().join("2025-04-01.md"), "no heading\n").unwrap();
+        fs::write(dir.path().join("2024-12-31.md"), "# Year end\n").unwrap();
+        fs::write(dir.path().join("scratch.md"), "# Scratch\n").unwrap();
+
+        write_index(&config, false).unwrap();
+        let index = fs::read_to_string(index_path(&config)).unwrap();
+        assert_eq!(
+            index,
+            "# Index\n\n## 2024\n\n### December\n\n\
+             - 2024-12-31: [Year end](2024-12-31.md)\n\n## 2025\n\n### April\n\n\
+             - 2025-04-01: [2025-04-01](2025-04-01.md)\n\
+             - 2025-04-09: [Planning](2025-04-09.md)\n"
+        );
+
+        // a second run leaves the file as it was
+        write_index(&config, false).unwrap();
+        assert_eq!(fs::read_to_string(index_path(&config)).unwrap(), index);
+    }
+
+    #[test]
+    fn test_use_template_picks_named_template() {
+        let dir = tempdir().unwrap();
+        let meeting = dir.path().join("meeting.md");
+        fs::write(&meeting, "# Meeting {{date}}\n## Attendees\n").unwrap();
+        let mut config = Config {
+            templates: BTreeMap::from([("meeting".to_string(), meeting.clone())]),
+            ..Config::new(dir.path().to_path_buf())
+        };
+
+        let err = config.use_template("standup").unwrap_err();
+        assert!(err.to_string().contains("expected one of: meeting"));
+        assert_eq!(config.template, None);
+
+        config.use_template("meeting").unwrap();
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        create_note_if_missing(&config, &path, date).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Meeting 2025-04-09\n## Attendees\n"
+        );
+    }
+
+    #[test]
+    fn test_search_notes_limit_adds_footer() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        for day in 1..=3 {
+            fs::write(dir.path().join(format!("2025-04-0{}.md", day)), "rust\n").unwrap();
+        }
+        let matcher = Matcher::new("rust", false, false).unwrap();
+
+        let mut out = Vec::new();
+        let range = DateRange::default();
+        let total = search_notes(
+            &config,
+            &matcher,
+            SearchMode::Lines(0),
+            range,
+            Some(1),
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(total, 3);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("1:rust").count(), 1);
+        assert!(out.ends_with("... and 2 more\n"));
+
+        let mut out = Vec::new();
+        search_notes(
+            &config,
+            &matcher,
+            SearchMode::Lines(0),
+            range,
+            Some(3),
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("more"));
+        assert_eq!(more_footer(0), None);
+    }
+
+    #[test]
+    fn test_matcher_highlight_paints_matches() {
+        let literal = Matcher::new("rust", true, false).unwrap();
+        assert_eq!(
+            literal.highlight("Rust and rust", true),
+            "\x1b[1;31mRust\x1b[0m and \x1b[1;31mrust\x1b[0m"
+        );
+        assert_eq!(literal.highlight("Rust and rust", false), "Rust and rust");
+        let regex = Matcher::new(r"\d+", false, true).unwrap();
+        assert_eq!(regex.highlight("a 12 b", true), "a \x1b[1;31m12\x1b[0m b");
+
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+
+    #[test]
+    fn test_is_empty_note_detects_stubs() {
+        let config = Config::new(PathBuf::from("/notes"));
+        let path = Path::new("/notes/2025-04-09.md");
+        assert!(is_empty_note(&config, path, "# 2025-04-09.md\n\n"));
+        assert!(is_empty_note(&config, path, "# 2025-04-09\n"));
+        assert!(is_empty_note(&config, path, "  \n"));
+        assert!(is_empty_note(
+            &config,
+            path,
+            "---\ndate: 2025-04-09\ntags: []\n---\n\n# 2025-04-09.md\n\n"
+        ));
+        assert!(!is_empty_note(
+            &config,
+            path,
+            "# 2025-04-09.md\n\n- buy milk\n"
+        ));
+        assert!(!is_empty_note(&config, path, "# Planning\n"));
+        assert!(!is_empty_note(
+            &config,
+            path,
+            "---\ndate: 2025-04-09\ntags: [work]\n---\n# 2025-04-09.md\n"
+        ));
+    }
+
+    #[test]
+    fn test_clean_notes_removes_only_stubs() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let stub = dir.path().join("2025-04-09.md");
+        let kept = dir.path().join("2025-04-10.md");
+        fs::write(&stub, "# 2025-04-09.md\n\n").unwrap();
+        fs::write(&kept, "# 2025-04-10.md\n\nwords\n").unwrap();
+
+        clean_notes(&config, true, true).unwrap();
+        assert!(stub.exists());
+        clean_notes(&config, true, false).unwrap();
+        assert!(!stub.exists());
+        assert!(kept.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_after_edit_passes_note_path() {
+        let dir = tempdir().unwrap();
+        let note = dir.path().join("2025-04-09.md");
+        let log = dir.path().join("hook.log");
+        let mut config = Config {
+            after_edit: Some(format!("echo \"$NN_NOTE $1\" > {}", log.display())),
+            ..Config::new(dir.path().to_path_buf())
+        };
+
+        run_after_edit(&config, &note).unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            format!("{} {}\n", note.display(), note.display())
+        );
+
+        // a failing hook is only a warning
+        config.after_edit = Some("exit 3".to_string());
+        run_after_edit(&config, &note).unwrap();
+    }
+
+    #[test]
+    fn test_sync_notes_pushes_to_remote() {
+        let dir = tempdir().unwrap();
+        let remote = dir.path().join("remote.git");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).unwrap();
+        let config = Config {
+            git_autocommit: true,
+            ..Config::new(notes.clone())
+        };
+        // not a repository, so there is nothing to do
+        sync_notes(&config, false).unwrap();
+
+        if !git(&config, &["init", "--quiet"]).unwrap_or(false) {
+            return; // git is not installed
+        }
+        git(&config, &["config", "user.email", "nn@example.com"]).unwrap();
+        git(&config, &["config", "user.name", "nn"]).unwrap();
+        // no remote yet, also skipped
+        sync_notes(&config, false).unwrap();
+
+        let remote_arg = remote.to_string_lossy();
+        assert!(git(&config, &["init", "--quiet", "--bare", &remote_arg]).unwrap());
+        git(&config, &["remote", "add", "origin", &remote_arg]).unwrap();
+        fs::write(notes.join("2025-04-09.md"), "# note\n").unwrap();
+        autocommit(&config, "nn: update 2025-04-09").unwrap();
+        let branch = git_output(&config, &["branch", "--show-current"]).unwrap();
+        let branch = String::from_utf8_lossy(&branch.stdout).trim().to_string();
+        git(&config, &["push", "--quiet", "-u", "origin", &branch]).unwrap();
+
+        fs::write(notes.join("2025-04-10.md"), "# another\n").unwrap();
+        sync_notes(&config, false).unwrap();
+
+        let log = Command::new("git")
+            .arg("--git-dir")
+            .arg(&remote)
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout).trim(),
+            "nn: sync\nnn: update 2025-04-09"
+        );
+    }
+
+    #[test]
+    fn test_note_extension_is_used_for_paths_and_walks() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            note_extension: "org".to_string(),
+            ..Config::new(dir.path().to_path_buf())
+        };
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
+        assert_eq!(path, dir.path().join("2025-04-09.org"));
+
+        create_note_if_missing(&config, &path, date).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "* 2025-04-09\n\n");
+        assert_eq!(note_title(&path).unwrap().as_deref(), Some("2025-04-09"));
+        assert!(is_empty_note(
+            &config,
+            &path,
+            &fs::read_to_string(&path).unwrap()
+        ));
 
-    if !config_file.exists() {
-        fs::create_dir_all(&config_dir).unwrap();
-        let toml_str = toml::to_string(&default).unwrap();
-        fs::write(&config_file, toml_str).unwrap();
-        fs::create_dir_all(&default.notes_dir).unwrap();
-        default
-    } else {
-        let toml_str = fs::read_to_string(&config_file).unwrap();
-        // check against toml keys
-        match toml::from_str(&toml_str) {
-            Ok(t) => t,
-            Err(e) => {
-                eprintln!("Error loading nn config: {}", e);
-                // ask to continue [y/n]
-                let mut input = String::new();
-                println!("Continue with default config? [y/n]");
-                io::stdin()
-                    .read_line(&mut input)
-                    .expect("Failed to read line");
-                if input.trim().to_lowercase() != "y" {
-                    // just exit here
-                    std::process::exit(1);
-                } else {
-                    default
-                }
-            }
-        }
+        fs::write(dir.path().join("2025-04-10.md"), "# markdown\n").unwrap();
+        assert_eq!(note_paths(&config).unwrap(), [path]);
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    notes_dir: PathBuf,
-    editor: String,
-}
+    #[test]
+    fn test_write_note_refuses_existing_unless_asked() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let date = jiff::civil::date(2025, 4, 9);
+        let path = get_note_path(&config, date);
 
-#[derive(Parser)]
-#[command(name = "nn", version, about = "A normal notes tool")]
-struct Cli {
-    #[command(subcommand)]
-    command: Option<Commands>,
-}
+        write_note(&config, date, "- first\n", false, false, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# 2025-04-09\n\n- first\n"
+        );
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Delete a note
-    Delete { date: String },
-    /// List all notes
-    List,
-    /// Search all notes for a string
-    Search { query: String },
-    /// Show all tags used in notes
-    Tags,
-}
+        assert!(write_note(&config, date, "- second\n", false, false, false).is_err());
+        write_note(&config, date, "- second\n", true, false, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# 2025-04-09\n\n- first\n- second\n"
+        );
 
-/// Get the path to the note file for a given date
-fn get_note_path(config: &Config, date: &str) -> PathBuf {
-    config.notes_dir.join(format!("{}.md", date))
-}
+        write_note(&config, date, "# Own heading\nbody\n", false, true, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Own heading\nbody\n");
+    }
 
-/// Create a note file with a basic heading if it does not already exist
-fn create_note_if_missing(path: &PathBuf) -> io::Result<()> {
-    if !path.exists() {
-        fs::write(
-            path,
-            format!("# {}\n\n", path.file_name().unwrap().to_string_lossy()),
-        )?;
+    #[test]
+    #[cfg(unix)]
+    fn test_read_walked_note_warns_or_fails_with_strict() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::new(dir.path().to_path_buf());
+        // a dangling symlink is walked like a note but cannot be read
+        let bad = dir.path().join("2025-04-09.md");
+        std::os::unix::fs::symlink(dir.path().join("missing"), &bad).unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "#work\n").unwrap();
+
+        assert_eq!(read_walked_note(&config, &bad).unwrap(), None);
+        assert_eq!(count_tags(&config).unwrap().get("#work"), Some(&1));
+
+        config.strict = true;
+        let err = read_walked_note(&config, &bad).unwrap_err();
+        assert!(err.to_string().contains("2025-04-09.md"));
+        assert!(count_tags(&config).is_err());
     }
-    Ok(())
-}
 
-/// Open the given file in the configured text editor
-fn open_editor(path: &PathBuf, config: &Config) {
-    let editor = &config.editor;
-    Command::new(editor).arg(path).status().unwrap();
-}
+    #[test]
+    fn test_read_walked_note_decodes_invalid_utf8_lossily() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let path = dir.path().join("2025-04-09.md");
+        fs::write(&path, b"caf\xe9 #work\nrust\n").unwrap();
 
-/// Print a list of all notes in the configured notes directory
-fn list_notes(config: &Config) {
-    let entries = fs::read_dir(&config.notes_dir).unwrap();
-    for entry in entries.flatten() {
-        if let Some(name) = entry.path().to_str() {
-            println!("{}", name);
-        }
+        assert_eq!(
+            read_walked_note(&config, &path).unwrap().as_deref(),
+            Some("caf\u{fffd} #work\nrust\n")
+        );
+        let matcher = Matcher::new("rust", false, false).unwrap();
+        let mut out = Vec::new();
+        let range = DateRange::default();
+        assert_eq!(
+            search_notes(
+                &config,
+                &matcher,
+                SearchMode::Lines(0),
+                range,
+                None,
+                false,
+                &mut out
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(count_tags(&config).unwrap().get("#work"), Some(&1));
     }
-}
 
-/// Delete the note file corresponding to the given date, if it exists
-fn delete_note(config: &Config, date: &str) {
-    let path = get_note_path(config, date);
-    if path.exists() {
-        fs::remove_file(path).unwrap();
-        eprintln!("Deleted note for {}", date);
-    } else {
-        eprintln!("No note found for {}", date);
+    #[test]
+    fn test_diff_note_needs_git_repo() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let err = diff_note(&config, jiff::civil::date(2025, 4, 9), None).unwrap_err();
+        assert!(err.to_string().contains("not a git repository"));
     }
-}
 
-/// Search all notes for a query string and print matching notes with content
-fn search_notes(config: &Config, query: &str) {
-    let entries = fs::read_dir(&config.notes_dir).unwrap();
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if let Ok(contents) = fs::read_to_string(&path) {
-            if contents.contains(query) {
-                eprintln!("{}:\n{}", path.display(), contents);
-            }
-        }
+    #[test]
+    fn test_changed_lines_pairs_before_and_after() {
+        let before = "# note\n#wip one\nplain\nmore #wip\n";
+        let (after, _) = replace_tag(before, "#wip", "#done");
+        assert_eq!(
+            changed_lines(before, &after),
+            [(2, "#wip one", "#done one"), (4, "more #wip", "more #done")]
+        );
     }
-}
 
-/// Extract and print all unique tags (e.g. #rust, #todo) used in notes
-fn extract_tags(config: &Config) {
-    let tag_re = Regex::new(r"#\w+").unwrap();
-    let mut tags = std::collections::HashSet::new();
-    let entries = fs::read_dir(&config.notes_dir).unwrap();
+    #[test]
+    fn test_replace_frontmatter_tag_rewrites_declared_tags() {
+        let inline = "---\ntitle: wip list\ntags: [wip, \"wipe\", '#wip']\n---\n# wip\n";
+        let (after, replaced) = replace_frontmatter_tag(inline, "wip", "done");
+        assert_eq!(replaced, 2);
+        assert_eq!(
+            after,
+            "---\ntitle: wip list\ntags: [done, \"wipe\", '#done']\n---\n# wip\n"
+        );
+        assert_eq!(
+            changed_lines(inline, &after),
+            [(
+                3,
+                "tags: [wip, \"wipe\", '#wip']",
+                "tags: [done, \"wipe\", '#done']"
+            )]
+        );
 
-    for entry in entries.flatten() {
-        if let Ok(contents) = fs::read_to_string(entry.path()) {
-            for tag in tag_re.find_iter(&contents) {
-                tags.insert(tag.as_str().to_string());
-            }
-        }
+        let block = "---\ntags:\n  - wip\n  - other\nnotes:\n  - wip\n---\n- wip\n";
+        let (after, replaced) = replace_frontmatter_tag(block, "wip", "done");
+        assert_eq!(replaced, 1);
+        assert_eq!(
+            after,
+            "---\ntags:\n  - done\n  - other\nnotes:\n  - wip\n---\n- wip\n"
+        );
+        assert_eq!(note_tags(&after), ["#done", "#other"]);
     }
 
-    for tag in tags {
-        println!("{}", tag);
+    #[test]
+    fn test_rename_tag_updates_frontmatter_tags() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let path = dir.path().join("2025-04-09.md");
+        fs::write(&path, "---\ntags: [wip]\n---\n# note\n#wip\n").unwrap();
+
+        rename_tag(&config, "wip", "in-progress", true, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\ntags: [wip]\n---\n# note\n#wip\n"
+        );
+        rename_tag(&config, "wip", "in-progress", false, false).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(note_tags(&contents), ["#in-progress", "#in-progress"]);
     }
-}
 
-fn main() {
-    let cli = Cli::parse();
-    let config = load_or_init_config();
+    #[test]
+    fn test_pick_random_note_weights_old_notes() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let today = jiff::civil::date(2025, 4, 10);
+        let old = dir.path().join("2024-04-10.md");
+        let new = dir.path().join("2025-04-10.md");
+        let paths = [old.clone(), new.clone()];
+        let mut rng = fastrand::Rng::with_seed(7);
 
-    match cli.command {
-        Some(Commands::Delete { date }) => delete_note(&config, &date),
-        Some(Commands::List) => list_notes(&config),
-        Some(Commands::Search { query }) => search_notes(&config, &query),
-        Some(Commands::Tags) => extract_tags(&config),
-        None => {
-            // Default: edit today's note
-            let date = Zoned::now().strftime("%Y-%m-%d").to_string();
-            let path = get_note_path(&config, &date);
-            create_note_if_missing(&path).unwrap();
-            open_editor(&path, &config);
+        assert_eq!(pick_random_note(&config, &[], false, today, &mut rng), None);
+        // the year-old note has weight 366 against 1 for today's
+        let picks = (0..100)
+            .filter(|_| {
+                pick_random_note(&config, &paths, true, today, &mut rng) == Some(old.clone())
+            })
+            .count();
+        assert!(picks > 90);
+        let uniform = (0..100)
+            .filter(|_| {
+                pick_random_note(&config, &paths, false, today, &mut rng) == Some(new.clone())
+            })
+            .count();
+        assert!((20..80).contains(&uniform));
+    }
+
+    #[test]
+    fn test_on_this_day_finds_earlier_years() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        for name in ["2022-04-09", "2023-04-09", "2023-04-10", "2025-04-09"] {
+            fs::write(dir.path().join(format!("{}.md", name)), "# n\n").unwrap();
         }
+        let notes = on_this_day(&config, jiff::civil::date(2025, 4, 9)).unwrap();
+        let dates: Vec<String> = notes.iter().map(|(date, _)| date.to_string()).collect();
+        assert_eq!(dates, ["2023-04-09", "2022-04-09"]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::{self, File};
-    use std::io::Write;
-    use tempfile::tempdir;
+    #[test]
+    fn test_exit_codes_for_error_kinds() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let missing = read_note(&config, jiff::civil::date(2025, 4, 9)).unwrap_err();
+        assert_eq!(missing.to_string(), "no note found for 2025-04-09");
+        assert_eq!(exit_code(&missing), 2);
+
+        let config_error: Box<dyn Error> = NnError::config("bad toml").into();
+        assert_eq!(exit_code(config_error.as_ref()), 3);
+        let date_error = resolve_date("2025-13-01", jiff::civil::date(2025, 4, 9))
+            .map_err(NnError::InvalidDate)
+            .unwrap_err();
+        assert_eq!(exit_code(&date_error), 4);
+        assert_eq!(exit_code(&io::Error::other("boom")), 1);
+    }
 
     #[test]
-    fn test_get_note_path() {
+    fn test_expand_aliases() {
+        let aliases = BTreeMap::from([
+            ("journal".to_string(), "new --template journal".to_string()),
+            ("week".to_string(), "list --from -7d".to_string()),
+            ("lastweek".to_string(), "week --to '-7d'".to_string()),
+            ("list".to_string(), "search shadowed".to_string()),
+            ("ping".to_string(), "pong".to_string()),
+            ("pong".to_string(), "ping".to_string()),
+        ]);
+        let expand = |args: &[&str]| {
+            let args = args.iter().map(Into::into).collect();
+            expand_aliases(args, &aliases).map(|args| {
+                args.into_iter()
+                    .map(|arg| arg.into_string().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            expand(&["nn", "--profile", "work", "journal", "-v"]).unwrap(),
+            [
+                "nn",
+                "--profile",
+                "work",
+                "new",
+                "--template",
+                "journal",
+                "-v"
+            ]
+        );
+        assert_eq!(
+            expand(&["nn", "lastweek"]).unwrap(),
+            ["nn", "list", "--from", "-7d", "--to", "-7d"]
+        );
+        // built-in subcommands can't be redefined
+        assert_eq!(expand(&["nn", "list"]).unwrap(), ["nn", "list"]);
+        // the value of a global option isn't taken for a subcommand
+        assert_eq!(
+            expand(&["nn", "--editor", "week"]).unwrap(),
+            ["nn", "--editor", "week"]
+        );
+        assert_eq!(
+            expand(&["nn", "ping"]).unwrap_err(),
+            "alias loop: ping -> pong -> ping"
+        );
+    }
+
+    #[test]
+    fn test_porcelain_search_and_count_are_tab_separated() {
+        let dir = tempdir().unwrap();
         let config = Config {
-            notes_dir: PathBuf::from("/tmp/my-notes"),
-            editor: "nano".to_string(),
+            porcelain: true,
+            ..Config::new(dir.path().to_path_buf())
+        };
+        let first = dir.path().join("2025-04-09.md");
+        let second = dir.path().join("2025-04-10.md");
+        fs::write(&first, "# a\nbefore\nrust\there\nafter\n").unwrap();
+        fs::write(&second, "# b\nrust\nmore rust\n").unwrap();
+        let matcher = Matcher::new("rust", false, false).unwrap();
+
+        // context, colors and the --limit footer are left out
+        let mut out = Vec::new();
+        search_notes(
+            &config,
+            &matcher,
+            SearchMode::Lines(1),
+            DateRange::default(),
+            Some(1),
+            true,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{0}\t2\trust\n{0}\t3\tmore rust\n", second.display())
+        );
+
+        let mut out = Vec::new();
+        count_matches(&config, &matcher, DateRange::default(), None, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\t2\n{}\t1\n", second.display(), first.display())
+        );
+    }
+
+    #[test]
+    fn test_count_total() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "# a\nthree words #rust\n").unwrap();
+        fs::write(dir.path().join("2025-04-10.md"), "# b\n#rust and #cli\n").unwrap();
+        fs::write(scratch_path(&config), "# Scratch\nnot counted\n").unwrap();
+
+        assert_eq!(count_total(&config, CountKind::Notes).unwrap(), 2);
+        assert_eq!(count_total(&config, CountKind::Tags).unwrap(), 2);
+        assert_eq!(count_total(&config, CountKind::Words).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_attach_file_copies_and_links() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().join("notes"));
+        let date = jiff::civil::date(2025, 4, 9);
+        let shot = dir.path().join("shot.png");
+        fs::write(&shot, "png").unwrap();
+        let report = dir.path().join("q1 report.pdf");
+        fs::write(&report, "pdf").unwrap();
+
+        attach_file(&config, date, &shot, false).unwrap();
+        attach_file(&config, date, &shot, false).unwrap();
+        attach_file(&config, date, &report, false).unwrap();
+
+        let attachments = attachments_dir(&config, date);
+        assert_eq!(
+            fs::read_to_string(attachments.join("shot.png")).unwrap(),
+            "png"
+        );
+        assert!(attachments.join("shot-1.png").exists());
+        let contents = read_note(&config, date).unwrap();
+        assert!(contents.ends_with(concat!(
+            "![shot.png](attachments/2025-04-09/shot.png)\n",
+            "![shot-1.png](attachments/2025-04-09/shot-1.png)\n",
+            "[q1 report.pdf](<attachments/2025-04-09/q1 report.pdf>)\n",
+        )));
+        // attachments are never mistaken for notes
+        fs::write(attachments.join("notes.md"), "# copied\n").unwrap();
+        assert_eq!(note_paths(&config).unwrap().len(), 1);
+
+        delete_note(&config, date, true, false, true, false).unwrap();
+        assert!(!attachments.exists());
+    }
+
+    #[test]
+    fn test_heading_format_titles_new_notes_with_the_date() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            heading_format: Some("%A, %-d %B %Y".to_string()),
+            ..Config::new(dir.path().to_path_buf())
         };
-        let date = "2025-04-09";
+        let date = jiff::civil::date(2025, 4, 9);
         let path = get_note_path(&config, date);
-        assert_eq!(path, PathBuf::from("/tmp/my-notes/2025-04-09.md"));
+
+        create_note_if_missing(&config, &path, date).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "# Wednesday, 9 April 2025\n\n");
+        assert!(is_empty_note(&config, &path, &contents));
+
+        let broken = Config {
+            heading_format: Some("%Q".to_string()),
+            ..Config::new(dir.path().to_path_buf())
+        };
+        assert_eq!(note_heading(&broken, &path, date), "2025-04-09");
+    }
+
+    #[test]
+    fn test_available_templates_include_template_dir() {
+        let dir = tempdir().unwrap();
+        let templates = dir.path().join("templates");
+        fs::create_dir(&templates).unwrap();
+        fs::write(templates.join("journal.md"), "# Journal {{date}}\n").unwrap();
+        fs::write(templates.join("meeting.md"), "# From the dir\n").unwrap();
+        fs::write(templates.join(".draft.md"), "hidden").unwrap();
+        let meeting = dir.path().join("meeting.md");
+        fs::write(&meeting, "# Meeting\n").unwrap();
+        let mut config = Config {
+            template_dir: Some(templates.clone()),
+            templates: BTreeMap::from([("meeting".to_string(), meeting.clone())]),
+            ..Config::new(dir.path().to_path_buf())
+        };
+
+        assert_eq!(
+            config.available_templates().unwrap(),
+            BTreeMap::from([
+                ("journal".to_string(), templates.join("journal.md")),
+                ("meeting".to_string(), meeting),
+            ])
+        );
+        config.use_template("journal").unwrap();
+        assert_eq!(config.template, Some(templates.join("journal.md")));
+        let err = config.use_template("standup").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected one of: journal, meeting"));
+    }
+
+    #[test]
+    fn test_ignore_globs_skip_notes_and_directories() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            ignore: vec!["*.log.md".to_string(), "generated".to_string()],
+            ..Config::new(dir.path().to_path_buf())
+        };
+        let kept = dir.path().join("2025-04-09.md");
+        fs::write(&kept, "#rust\n").unwrap();
+        fs::write(dir.path().join("build.log.md"), "#noise\n").unwrap();
+        fs::create_dir(dir.path().join("generated")).unwrap();
+        fs::write(dir.path().join("generated").join("api.md"), "#noise\n").unwrap();
+
+        assert_eq!(note_paths(&config).unwrap(), [kept]);
+        assert_eq!(count_tags(&config).unwrap().len(), 1);
+
+        let broken = Config {
+            ignore: vec!["[".to_string()],
+            ..Config::new(dir.path().to_path_buf())
+        };
+        assert!(note_paths(&broken).is_err());
+    }
+
+    #[test]
+    fn test_search_notes_files_only_and_quiet() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let first = dir.path().join("2025-04-09.md");
+        let second = dir.path().join("2025-04-10.md");
+        fs::write(&first, "# a\nrust\n").unwrap();
+        fs::write(&second, "# b\nrust\nrust again\n").unwrap();
+        fs::write(dir.path().join("2025-04-11.md"), "# c\npython\n").unwrap();
+        let matcher = Matcher::new("rust", false, false).unwrap();
+        let range = DateRange::default();
+
+        let mut out = Vec::new();
+        let total = search_notes(
+            &config,
+            &matcher,
+            SearchMode::Files,
+            range,
+            None,
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\n{}\n", second.display(), first.display())
+        );
+
+        let mut out = Vec::new();
+        let total = search_notes(
+            &config,
+            &matcher,
+            SearchMode::Quiet,
+            range,
+            None,
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(total, 1);
+        assert!(out.is_empty());
+        let missing = Matcher::new("haskell", false, false).unwrap();
+        let total = search_notes(
+            &config,
+            &missing,
+            SearchMode::Quiet,
+            range,
+            None,
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(total, 0);
+        assert_eq!(exit_code(&NnError::NoMatch), 1);
+    }
+
+    #[test]
+    fn test_heading_setting_picks_what_new_notes_start_with() {
+        let dir = tempdir().unwrap();
+        let date = jiff::civil::date(2025, 4, 9);
+        let contents = |heading, heading_format: Option<&str>| {
+            let config = Config {
+                heading,
+                heading_format: heading_format.map(str::to_string),
+                date_format: "%Y/%m/%d".to_string(),
+                ..Config::new(dir.path().to_path_buf())
+            };
+            new_note_contents(&config, &get_note_path(&config, date), date)
+        };
+
+        assert_eq!(contents(None, None), "# 2025/04/09\n\n");
+        assert_eq!(contents(None, Some("%-d %b")), "# 9 Apr\n\n");
+        assert_eq!(
+            contents(Some(HeadingSource::Filename), Some("%-d %b")),
+            "# 2025/04/09\n\n"
+        );
+        assert_eq!(
+            contents(Some(HeadingSource::Date), None),
+            "# 2025-04-09\n\n"
+        );
+        assert_eq!(contents(Some(HeadingSource::None), None), "");
+
+        let (config, _) =
+            parse_config("notes_dir = \"/notes\"\neditor = \"vi\"\nheading = \"none\"\n").unwrap();
+        assert_eq!(config.heading_source(), HeadingSource::None);
     }
 
     #[test]
     fn test_create_note_if_missing_creates_file() {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("test-note.md");
+        let config = Config::new(dir.path().to_path_buf());
 
-        create_note_if_missing(&file_path).unwrap();
+        create_note_if_missing(&config, &file_path, jiff::civil::date(2025, 4, 9)).unwrap();
         assert!(file_path.exists());
 
         let content = fs::read_to_string(file_path).unwrap();
-        assert!(content.contains("# test-note.md"));
+        assert!(content.starts_with("# test-note\n"));
+    }
+
+    #[test]
+    fn test_create_note_if_missing_uses_template() {
+        let dir = tempdir().unwrap();
+        let template = dir.path().join("template.md");
+        fs::write(&template, "# {{title}}\n{{weekday}} {{date}}\n").unwrap();
+        let mut config = Config {
+            template: Some(template),
+            ..Config::new(dir.path().to_path_buf())
+        };
+        let date = jiff::civil::date(2025, 4, 9);
+
+        let path = get_note_path(&config, date);
+        create_note_if_missing(&config, &path, date).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# 2025-04-09\nWednesday 2025-04-09\n"
+        );
+
+        // a missing template falls back to the default heading
+        config.template = Some(dir.path().join("missing.md"));
+        let path = get_note_path(&config, jiff::civil::date(2025, 4, 10));
+        create_note_if_missing(&config, &path, jiff::civil::date(2025, 4, 10)).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# 2025-04-10"));
     }
 
     #[test]
@@ -198,18 +7278,108 @@ mod tests {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("test-note.md");
         fs::write(&file_path, "original content").unwrap();
+        let config = Config::new(dir.path().to_path_buf());
 
-        create_note_if_missing(&file_path).unwrap();
+        create_note_if_missing(&config, &file_path, jiff::civil::date(2025, 4, 9)).unwrap();
         let content = fs::read_to_string(&file_path).unwrap();
         assert_eq!(content, "original content"); // File should remain unchanged
     }
 
+    #[test]
+    fn test_note_paths_walks_subdirectories_and_skips_other_files() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::create_dir_all(dir.path().join("projects/rust")).unwrap();
+        fs::write(dir.path().join("2025-04-09.md"), "").unwrap();
+        fs::write(dir.path().join("projects/rust/ideas.md"), "").unwrap();
+        fs::write(dir.path().join("projects/diagram.png"), "").unwrap();
+
+        let mut names: Vec<String> = note_paths(&config)
+            .unwrap()
+            .iter()
+            .map(|path| note_name(&config, path))
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["2025-04-09", "projects/rust/ideas"]);
+    }
+
+    #[test]
+    fn test_note_entry_parses_date_from_filename() {
+        let dir = tempdir().unwrap();
+        let dated = dir.path().join("2025-04-09.md");
+        let undated = dir.path().join("ideas.md");
+        fs::write(&dated, "hello").unwrap();
+        fs::write(&undated, "").unwrap();
+
+        let config = Config::new(dir.path().to_path_buf());
+        let entry = NoteEntry::from_path(&config, dated).unwrap();
+        assert_eq!(entry.date.as_deref(), Some("2025-04-09"));
+        assert_eq!(entry.size_bytes, 5);
+        assert!(entry.modified.is_some());
+        assert_eq!(NoteEntry::from_path(&config, undated).unwrap().date, None);
+    }
+
+    #[test]
+    fn test_sort_notes_by_date_with_undated_last() {
+        let config = Config::new(PathBuf::new());
+        let mut paths: Vec<PathBuf> = ["b.md", "2025-01-02.md", "a.md", "2024-12-31.md"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        sort_notes(&config, &mut paths, false);
+        assert_eq!(
+            paths,
+            ["2025-01-02.md", "2024-12-31.md", "a.md", "b.md"].map(PathBuf::from)
+        );
+
+        sort_notes(&config, &mut paths, true);
+        assert_eq!(
+            paths,
+            ["2024-12-31.md", "2025-01-02.md", "a.md", "b.md"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn test_format_ago() {
+        assert_eq!(format_ago(5), "just now");
+        assert_eq!(format_ago(60), "1 minute ago");
+        assert_eq!(format_ago(7200), "2 hours ago");
+        assert_eq!(format_ago(3 * 86400 + 10), "3 days ago");
+    }
+
+    #[test]
+    fn test_matcher_case_sensitivity() {
+        let sensitive = Matcher::new("Rust", false, false).unwrap();
+        assert!(sensitive.is_match("Learning Rust today"));
+        assert!(!sensitive.is_match("learning rust today"));
+
+        let insensitive = Matcher::new("Rust", true, false).unwrap();
+        assert!(insensitive.is_match("learning RUST today"));
+    }
+
+    #[test]
+    fn test_matcher_regex() {
+        let matcher = Matcher::new(r"TODO:.*\d{4}", false, true).unwrap();
+        assert!(matcher.is_match("TODO: renew by 2025"));
+        assert!(!matcher.is_match("TODO: someday"));
+        assert!(Matcher::new("(unclosed", false, true).is_err());
+    }
+
+    #[test]
+    fn test_context_ranges_merge_overlapping_windows() {
+        assert_eq!(context_ranges(&[3], 0, 10), vec![(3, 3)]);
+        assert_eq!(context_ranges(&[0, 9], 2, 10), vec![(0, 2), (7, 9)]);
+        assert_eq!(context_ranges(&[2, 5], 1, 10), vec![(1, 6)]);
+    }
+
     #[test]
     fn test_extract_tags_finds_all_tags() {
         let dir = tempdir().unwrap();
         let config = Config {
             notes_dir: dir.path().to_path_buf(),
             editor: "nano".into(),
+            ..Config::new(PathBuf::new())
         };
 
         let mut file1 = File::create(dir.path().join("note1.md")).unwrap();
@@ -236,4 +7406,226 @@ mod tests {
 
         assert_eq!(captured_tags, expected_tags);
     }
+
+    #[test]
+    fn test_count_tags_sorted_by_frequency() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("note1.md"), "#rust #cli #rust").unwrap();
+        fs::write(dir.path().join("note2.md"), "#dev #rust #cli #alpha").unwrap();
+
+        let counts = sort_tag_counts(count_tags(&config).unwrap());
+        let expected: Vec<(String, usize)> =
+            [("#rust", 3), ("#cli", 2), ("#alpha", 1), ("#dev", 1)]
+                .iter()
+                .map(|(tag, n)| (tag.to_string(), *n))
+                .collect();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_notes_with_tag_matches_whole_tags_in_date_order() {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "more #rust").unwrap();
+        fs::write(dir.path().join("2025-01-01.md"), "#rust begins").unwrap();
+        fs::write(dir.path().join("2025-02-01.md"), "#rustacean only").unwrap();
+
+        let names: Vec<String> = notes_with_tag(&config, "rust")
+            .unwrap()
+            .iter()
+            .map(|path| note_name(&config, path))
+            .collect();
+        assert_eq!(names, vec!["2025-01-01", "2025-04-09"]);
+        assert_eq!(notes_with_tag(&config, "#rust").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_find_tags_hierarchical_and_hyphenated() {
+        let tags: Vec<&str> =
+            find_tags("Started #work/project-x, still #to-do and #in_progress. Done #tag.")
+                .collect();
+        assert_eq!(
+            tags,
+            vec!["#work/project-x", "#to-do", "#in_progress", "#tag"]
+        );
+    }
+
+    #[test]
+    fn test_replace_tag_only_matches_whole_tags() {
+        let (updated, replaced) = replace_tag("#wip and #wipe, #wip.", "#wip", "#in-progress");
+        assert_eq!(updated, "#in-progress and #wipe, #in-progress.");
+        assert_eq!(replaced, 2);
+    }
+
+    #[test]
+    fn test_parse_frontmatter_fields_and_tags() {
+        let note = "---\ntitle: Planning\ntags: [rust, \"cli\"]\n---\n# Body #inline\n";
+        let frontmatter = parse_frontmatter(note).unwrap();
+        assert_eq!(frontmatter.tags, vec!["rust", "cli"]);
+        assert_eq!(
+            frontmatter.fields[0],
+            ("title".to_string(), "Planning".to_string())
+        );
+        assert_eq!(note_tags(note), vec!["#rust", "#cli", "#inline"]);
+
+        let block = "---\ntags:\n  - work/project-x\n  - todo\ndate: 2025-04-09\n---\n";
+        assert_eq!(
+            parse_frontmatter(block).unwrap().tags,
+            vec!["work/project-x", "todo"]
+        );
+
+        assert_eq!(parse_frontmatter("# No frontmatter\n"), None);
+        assert_eq!(parse_frontmatter("---\nunterminated: yes\n"), None);
+    }
+
+    #[test]
+    fn test_new_note_contents_with_frontmatter() {
+        let config = Config {
+            frontmatter: true,
+            ..Config::new(PathBuf::from("/tmp/notes"))
+        };
+        let date = jiff::civil::date(2025, 4, 9);
+        let contents = new_note_contents(&config, &get_note_path(&config, date), date);
+        assert!(contents.starts_with("---\ndate: 2025-04-09\ntags: []\n---\n"));
+        let frontmatter = parse_frontmatter(&contents).unwrap();
+        assert!(frontmatter.tags.is_empty());
+    }
+
+    #[test]
+    fn test_count_words_skips_headings() {
+        let note = "# 2025-04-09\n\nWrote some code today\n## Next\n- ship #it\n#tag line\n";
+        assert_eq!(count_words(note), 9);
+    }
+
+    #[test]
+    fn test_text_counts_strip_markdown() {
+        let note = "# Monday\n\n- [x] **ship** the _release_\n> quoted `code`\n```\nlet x;\n```\n";
+        assert_eq!(
+            strip_markdown(note),
+            "Monday\n\nship the release\nquoted code\nlet x;\n"
+        );
+        assert_eq!(
+            TextCounts::of(note),
+            TextCounts {
+                words: 8,
+                lines: 4,
+                chars: 35,
+            }
+        );
+    }
+
+    #[test]
+    fn test_current_streak_counts_back_from_today() {
+        let today = jiff::civil::date(2025, 3, 1);
+        let dates = [
+            jiff::civil::date(2025, 2, 20),
+            jiff::civil::date(2025, 2, 27),
+            jiff::civil::date(2025, 2, 28),
+            today,
+        ];
+        assert_eq!(current_streak(&dates, today), 3);
+        assert_eq!(current_streak(&dates[..3], today), 0);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_longest_streak_and_missing_dates_across_month_boundary() {
+        let d = jiff::civil::date;
+        let dates = [
+            d(2025, 1, 30),
+            d(2025, 1, 31),
+            d(2025, 2, 1),
+            d(2025, 2, 2),
+            d(2025, 2, 4),
+        ];
+        assert_eq!(longest_streak(&dates), 4);
+        assert_eq!(longest_streak(&[]), 0);
+        assert_eq!(
+            missing_dates(&dates, d(2025, 2, 5), 4),
+            vec![d(2025, 2, 3), d(2025, 2, 5)]
+        );
+    }
+
+    #[test]
+    fn test_note_title_and_fuzzy_matches() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("2025-04-09.md");
+        fs::write(&path, "---\ntags: []\n---\n# Sprint Planning\n\nbody").unwrap();
+        assert_eq!(
+            note_title(&path).unwrap().as_deref(),
+            Some("Sprint Planning")
+        );
+
+        assert!(fuzzy_matches("sprpln", "Sprint Planning"));
+        assert!(fuzzy_matches("2549", "2025-04-09"));
+        assert!(!fuzzy_matches("plsp", "Sprint Planning"));
+    }
+
+    #[test]
+    fn test_autocommit_commits_changes_in_git_repo() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            git_autocommit: true,
+            ..Config::new(dir.path().to_path_buf())
+        };
+        // not a repository yet, so this is skipped silently
+        autocommit(&config, "nn: skipped").unwrap();
+
+        if !git(&config, &["init", "--quiet"]).unwrap_or(false) {
+            return; // git is not installed
+        }
+        git(&config, &["config", "user.email", "nn@example.com"]).unwrap();
+        git(&config, &["config", "user.name", "nn"]).unwrap();
+        fs::write(dir.path().join("2025-04-09.md"), "# note").unwrap();
+        autocommit(&config, "nn: update 2025-04-09").unwrap();
+
+        let log = Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout).trim(),
+            "nn: update 2025-04-09"
+        );
+    }
+
+    #[test]
+    fn test_render_note_html_links_tags_outside_code() {
+        let html = render_note_html(
+            "---\ntags: [x]\n---\n# Day\n\nWorked on #rust.\n\n```\n#not-a-tag\n```\n",
+        );
+        assert!(html.contains("<h1>Day</h1>"));
+        assert!(html.contains("<a class=\"tag\" href=\"tags.html#tag-rust\">#rust</a>."));
+        assert!(html.contains("<code>#not-a-tag\n</code>"));
+        assert!(!html.contains("tags: [x]"));
+    }
+
+    #[test]
+    fn test_export_html_writes_pages_index_and_tags() {
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        fs::write(dir.path().join("2025-04-09.md"), "# Tuesday <3\n\n#rust").unwrap();
+
+        export_html(&config, out.path()).unwrap();
+        assert!(out.path().join("2025-04-09.html").exists());
+        let index = fs::read_to_string(out.path().join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"2025-04-09.html\">2025-04-09</a> Tuesday &lt;3"));
+        let tags = fs::read_to_string(out.path().join("tags.html")).unwrap();
+        assert!(tags.contains("<h2 id=\"tag-rust\">#rust</h2>"));
+        // the markdown source is left alone
+        assert_eq!(
+            fs::read_to_string(dir.path().join("2025-04-09.md")).unwrap(),
+            "# Tuesday <3\n\n#rust"
+        );
+    }
 }